@@ -1,8 +1,14 @@
 
 
 // Declare submodules
+pub mod file_utils;
 pub mod shader_compiler;
+pub mod shader_manifest;
+pub mod shader_watcher;
 
 
 // Re-export items if needed
+pub use file_utils::*;
 pub use shader_compiler::*;
+pub use shader_manifest::*;
+pub use shader_watcher::*;
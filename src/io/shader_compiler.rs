@@ -3,22 +3,56 @@ use mlog::*;
 use std::{
     fs,
     io::Result,
-    path::Path,
+    path::{Path, PathBuf},
     process::Command,
     io::Cursor,
     fs::File,
 };
 use ash::util::read_spv;
 
+use super::shader_manifest::{ShaderManifest, ShaderRecord};
+
 use std::io::{Read, Write,Error};
 
-// temp function which just compiles all shaders within directory, will eventually make compilation only occur with shaders which have been changed since compilation using spv comparision
+// Flags every shader is compiled with, recorded in the manifest so changing them recompiles everything.
+pub const COMPILER_FLAGS: &[&str] = &["-V"];  // Target SPIR-V output
+
+
+// Outcome of one incremental pass over resources/shaders.
+#[derive(Default)]
+pub struct ShaderCompileReport {
+    pub compiled: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,  // (source, compiler diagnostics)
+    pub up_to_date: usize,
+}
+
+
+// Compiles the shaders in resources/shaders whose .spv is out of date, see ShaderManifest. Fails if any of them
+// doesn't compile, used at startup where there is no previous pipeline to fall back to.
 pub fn compile_all_shaders() -> Result<()> {
     info!("Compiling shaders:");
 
-    let shaders_path = Path::new(env!("CARGO_MANIFEST_DIR"))
-        .join("resources")
-        .join("shaders");
+    let report = compile_changed_shaders()?;
+    for (path, diagnostics) in &report.failed {
+        crit!("Failed to compile shader: {}\n{}", path.to_str().expect("path malformed?"), diagnostics);
+    }
+    if !report.failed.is_empty() {
+        return Err(Error::new(std::io::ErrorKind::InvalidInput, "Shader Compilation Failed!"))
+    }
+
+    success!("Shader compilation successful :) ({} compiled, {} up to date)", report.compiled.len(), report.up_to_date);
+    Ok(())
+}
+
+
+// Recompiles every shader source whose manifest record no longer matches (source, includes, compiler version
+// or flags changed, or the .spv is gone). Failed shaders keep their old .spv and are retried on the next call.
+pub fn compile_changed_shaders() -> Result<ShaderCompileReport> {
+    let compiler_version = compiler_version()?;
+    let mut manifest = ShaderManifest::load(&ShaderManifest::default_path());
+    let mut report = ShaderCompileReport::default();
+
+    let shaders_path = shader_source_dir();
     // info!("Shader source directory: {:?}", shaders_path.to_str().unwrap_or(""));
 
     // Iterate over files in the directory
@@ -27,55 +61,96 @@ pub fn compile_all_shaders() -> Result<()> {
         let path = entry.path();
 
         // Only compile files with known shader extensions (e.g., .vert, .frag, .comp)
-        if let Some(extension) = path.extension() {
-            if extension == "vert" || extension == "frag" || extension == "comp" {
-                info!("    Compiling shader: {:?}", path.to_str().unwrap_or(""));
-                
-                let original_extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
-                let output_path = path.with_extension(format!("{}.spv", original_extension));
-                
-                // Invoke glslangValidator on the shader file
-                let output = Command::new("glslangValidator")
-                    .arg("-V") // Target SPIR-V output
-                    .arg(path.as_os_str())
-                    .arg("-o")
-                    .arg(output_path)
-                    .output();
-
-                // Check if the command was successful
-                match output {
-                    Ok(output) => {
-                        if !output.status.success() {
-                            crit!("Failed to compile shader: {}", path.to_str().expect("path malformed?"));
-                            return Err(Error::new(std::io::ErrorKind::InvalidInput, "Shader Compilation Failed!"))
-                        } else {
-                            info!("        Successfully compiled: {:?}", path.to_str().unwrap());
-                        }
-                    }
-                    Err(e) => { 
-                        crit!("Failed to run glslangValidator: {}", e);
-                        return Err(e);
-                        }
-                }
+        if !is_shader_source(&path) {
+            continue;
+        }
+
+        let record = ShaderRecord::current(&path, &compiler_version, COMPILER_FLAGS)?;
+        if manifest.is_up_to_date(&path, &spirv_path(&path), &record) {
+            report.up_to_date += 1;
+            continue;
+        }
+
+        info!("    Compiling shader: {:?}", path.to_str().unwrap_or(""));
+        match compile_shader(&path) {
+            Ok(_) => {
+                info!("        Successfully compiled: {:?}", path.to_str().unwrap());
+                manifest.record(&path, record);
+                report.compiled.push(path);
             }
+            Err(diagnostics) => report.failed.push((path, diagnostics)),
         }
     }
-    success!("Shader compilation successful :)");
-    Ok(())
+
+    if !report.compiled.is_empty() {
+        manifest.save();
+    }
+    Ok(report)
 }
 
 
-pub fn load_spirv_from_file(path: &str) -> Vec<u32> {
+// First line of `glslangValidator --version`, e.g. "Glslang Version: 11:14.0.0".
+fn compiler_version() -> Result<String> {
+    let output = Command::new("glslangValidator").arg("--version").output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("unknown").trim().to_string())
+}
+
+
+pub fn shader_source_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join("shaders")
+}
+
+
+pub fn is_shader_source(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("vert" | "frag" | "comp"))
+}
+
+
+pub fn spirv_path(source: &Path) -> PathBuf {
+    let original_extension = source.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    source.with_extension(format!("{}.spv", original_extension))
+}
+
+
+// Compiles one source next to itself as <name>.<stage>.spv, returning the output path or the compiler diagnostics.
+// glslangValidator doesn't touch the output on failure, so the previous .spv stays usable.
+pub fn compile_shader(path: &Path) -> std::result::Result<PathBuf, String> {
+    let output_path = spirv_path(path);
+
+    // Invoke glslangValidator on the shader file
+    let output = Command::new("glslangValidator")
+        .args(COMPILER_FLAGS)
+        .arg(path.as_os_str())
+        .arg("-o")
+        .arg(&output_path)
+        .output()
+        .map_err(|e| format!("Failed to run glslangValidator: {}", e))?;
+
+    if output.status.success() {
+        Ok(output_path)
+    } else {
+        // glslang reports errors on stdout
+        Err(format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        ).trim_end().to_string())
+    }
+}
+
+
+// Errors instead of panicking so a hot reload can keep its last good module, e.g. when the .spv is mid-write.
+pub fn load_spirv_from_file(path: &str) -> Result<Vec<u32>> {
     // Construct the path from the string
     let shader_path = Path::new(path);
 
     // Read shader SPIR-V file
-    let mut file = File::open(shader_path).expect("Failed to open shader file");
+    let mut file = File::open(shader_path)?;
     let mut bytes = Vec::new();
-    file.read_to_end(&mut bytes).expect("Failed to read shader file");
+    file.read_to_end(&mut bytes)?;
 
-    // Parse the SPIR-V file into a Vec<u32>
-    let spv_bytes = read_spv(&mut Cursor::new(&bytes)).expect("Failed to parse SPIR-V shader");
-
-    spv_bytes
-}
\ No newline at end of file
+    // Parse the SPIR-V file into a Vec<u32>, rejects a bad magic number or a length that isn't a whole word
+    read_spv(&mut Cursor::new(&bytes))
+}
@@ -0,0 +1,280 @@
+use mlog::*;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::file_utils::write_atomically;
+
+
+// Record of how every .spv in resources/shaders was produced, stored in target/cache/shader_manifest.json.
+// A shader is only recompiled when its source, one of its (transitive) #includes, the compiler version
+// or the compiler flags differ from what is recorded, or the .spv is missing.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ShaderManifest {
+    #[serde(default)]
+    shaders: BTreeMap<String, ShaderRecord>,  // source path -> how its .spv was built
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderRecord {
+    pub source_hash: String,
+    pub includes: BTreeMap<String, String>,  // include path -> content hash, "missing" if it couldn't be read
+    pub compiler_version: String,
+    pub flags: Vec<String>,
+}
+
+impl ShaderManifest {
+    pub fn default_path() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("cache")
+            .join("shader_manifest.json")
+    }
+
+    // A missing or unreadable manifest just means everything gets compiled once.
+    pub fn load(path: &Path) -> Self {
+        let manifest = fs::read_to_string(path)
+            .ok()
+            .and_then(|source| match serde_json::from_str::<ShaderManifest>(&source) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    crit!("Ignoring malformed shader manifest {:?}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self {
+            path: path.to_path_buf(),
+            ..manifest
+        }
+    }
+
+    // Written to a temporary file first, so an interrupted build never leaves a truncated manifest behind.
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(std::io::Error::other)
+            .and_then(|json| write_atomically(&self.path, json.as_bytes()));
+
+        if let Err(e) = result {
+            crit!("Failed to save shader manifest {:?}: {}", self.path, e);
+        }
+    }
+
+    pub fn is_up_to_date(&self, source: &Path, output: &Path, record: &ShaderRecord) -> bool {
+        output.exists() && self.shaders.get(&Self::key(source)) == Some(record)
+    }
+
+    pub fn record(&mut self, source: &Path, record: ShaderRecord) {
+        self.shaders.insert(Self::key(source), record);
+    }
+
+    fn key(source: &Path) -> String {
+        source.to_string_lossy().into_owned()
+    }
+}
+
+impl ShaderRecord {
+    // Hashes `source` and everything it includes as of now, to compare against the recorded build.
+    pub fn current(source: &Path, compiler_version: &str, flags: &[&str]) -> std::io::Result<Self> {
+        let source_hash = content_hash(&fs::read(source)?);
+
+        let includes = collect_includes(source)
+            .into_iter()
+            .map(|include| {
+                let hash = fs::read(&include).map_or_else(|_| "missing".to_string(), |bytes| content_hash(&bytes));
+                (include.to_string_lossy().into_owned(), hash)
+            })
+            .collect();
+
+        Ok(Self {
+            source_hash,
+            includes,
+            compiler_version: compiler_version.to_string(),
+            flags: flags.iter().map(|flag| flag.to_string()).collect(),
+        })
+    }
+}
+
+
+// Transitive `#include "file"` / `#include <file>` dependencies (GL_GOOGLE_include_directive), resolved
+// relative to the including file the way glslangValidator does.
+fn collect_includes(source: &Path) -> BTreeSet<PathBuf> {
+    let mut includes = BTreeSet::new();
+    let mut pending = vec![source.to_path_buf()];
+
+    while let Some(file) = pending.pop() {
+        let Ok(text) = fs::read_to_string(&file) else { continue };
+        let directory = file.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        for line in text.lines() {
+            let Some(name) = include_name(line) else { continue };
+
+            let include = directory.join(name);
+            if includes.insert(include.clone()) {
+                pending.push(include);
+            }
+        }
+    }
+
+    includes
+}
+
+// The file name of an `#include "file"` or `#include <file>` line, anything after the closing delimiter
+// (usually a comment) is ignored.
+fn include_name(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix("include")?.trim_start();
+    let closing = match rest.chars().next()? {
+        '"' => '"',
+        '<' => '>',
+        _ => return None,
+    };
+
+    let name = &rest[1..];
+    let name = &name[..name.find(closing)?];
+    (!name.is_empty()).then_some(name)
+}
+
+
+// FNV-1a, stable across Rust versions unlike DefaultHasher, so the manifest stays valid after a toolchain update.
+fn content_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh scratch directory per test, removed again when dropped.
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("neon_shader_manifest_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn content_hash_is_fnv1a() {
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+        assert_eq!(content_hash(b"foobar"), "85944171f73967e8");
+        assert_ne!(content_hash(b"ab"), content_hash(b"ba"));
+    }
+
+    #[test]
+    fn include_names_stop_at_the_closing_delimiter() {
+        assert_eq!(include_name(r#"#include "common.glsl""#), Some("common.glsl"));
+        assert_eq!(include_name(r#"  #include "common.glsl" // shared helpers"#), Some("common.glsl"));
+        assert_eq!(include_name("#include <lib/noise.glsl> /* 3d noise */"), Some("lib/noise.glsl"));
+        assert_eq!(include_name(r#"# include "spaced.glsl""#), Some("spaced.glsl"));
+        assert_eq!(include_name(r#"#include """#), None);
+        assert_eq!(include_name(r#"#include "unterminated"#), None);
+        assert_eq!(include_name("#include common.glsl"), None);
+        assert_eq!(include_name("#version 450"), None);
+        assert_eq!(include_name(r#"// #include "commented_out.glsl""#), None);
+    }
+
+    #[test]
+    fn collect_includes_is_transitive() {
+        let dir = ScratchDir::new("collect_includes");
+        let source = dir.write("shader.frag", "#version 450\n#include \"common.glsl\" // helpers\n#include <lib/noise.glsl>\n");
+        dir.write("common.glsl", "#include \"lib/noise.glsl\"\n");
+        dir.write("lib/noise.glsl", "#include \"hash.glsl\"\n");
+        dir.write("lib/hash.glsl", "float hash(float x) { return x; }\n");
+
+        let includes = collect_includes(&source);
+
+        let expected = [dir.0.join("common.glsl"), dir.0.join("lib/noise.glsl"), dir.0.join("lib/hash.glsl")];
+        assert_eq!(includes, expected.into_iter().collect::<BTreeSet<_>>());
+    }
+
+    #[test]
+    fn collect_includes_survives_cycles_and_missing_files() {
+        let dir = ScratchDir::new("include_cycles");
+        let source = dir.write("a.glsl", "#include \"b.glsl\"\n#include \"missing.glsl\"\n");
+        dir.write("b.glsl", "#include \"a.glsl\"\n");
+
+        let includes = collect_includes(&source);
+
+        let expected = [dir.0.join("a.glsl"), dir.0.join("b.glsl"), dir.0.join("missing.glsl")];
+        assert_eq!(includes, expected.into_iter().collect::<BTreeSet<_>>());
+    }
+
+    #[test]
+    fn is_up_to_date_tracks_sources_includes_and_settings() {
+        let dir = ScratchDir::new("up_to_date");
+        let source = dir.write("shader.vert", "#include \"common.glsl\"\nvoid main() {}\n");
+        let include = dir.write("common.glsl", "const float SCALE = 1.0;\n");
+        let output = dir.write("shader.vert.spv", "spirv");
+
+        let mut manifest = ShaderManifest::load(&dir.0.join("manifest.json"));
+        let record = ShaderRecord::current(&source, "glslang 1", &["-V"]).unwrap();
+        assert!(!manifest.is_up_to_date(&source, &output, &record));
+
+        manifest.record(&source, record.clone());
+        assert!(manifest.is_up_to_date(&source, &output, &record));
+
+        // settings
+        let other_version = ShaderRecord::current(&source, "glslang 2", &["-V"]).unwrap();
+        assert!(!manifest.is_up_to_date(&source, &output, &other_version));
+        let other_flags = ShaderRecord::current(&source, "glslang 1", &["-V", "-g"]).unwrap();
+        assert!(!manifest.is_up_to_date(&source, &output, &other_flags));
+
+        // an edited include invalidates the shader including it
+        fs::write(&include, "const float SCALE = 2.0;\n").unwrap();
+        let edited = ShaderRecord::current(&source, "glslang 1", &["-V"]).unwrap();
+        assert!(!manifest.is_up_to_date(&source, &output, &edited));
+
+        // and so does a deleted one
+        fs::remove_file(&include).unwrap();
+        let deleted = ShaderRecord::current(&source, "glslang 1", &["-V"]).unwrap();
+        assert_eq!(deleted.includes[&include.to_string_lossy().into_owned()], "missing");
+        assert!(!manifest.is_up_to_date(&source, &output, &deleted));
+
+        // a missing .spv always needs a rebuild
+        fs::remove_file(&output).unwrap();
+        assert!(!manifest.is_up_to_date(&source, &output, &record));
+    }
+
+    #[test]
+    fn save_round_trips_without_leaving_a_temp_file() {
+        let dir = ScratchDir::new("save");
+        let source = dir.write("shader.frag", "void main() {}\n");
+        let output = dir.write("shader.frag.spv", "spirv");
+        let path = dir.0.join("cache").join("manifest.json");
+
+        let record = ShaderRecord::current(&source, "glslang 1", &["-V"]).unwrap();
+        let mut manifest = ShaderManifest::load(&path);
+        manifest.record(&source, record.clone());
+        manifest.save();
+
+        assert!(path.exists());
+        assert!(!path.with_extension("tmp").exists());
+        assert!(ShaderManifest::load(&path).is_up_to_date(&source, &output, &record));
+    }
+}
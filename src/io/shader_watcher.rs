@@ -0,0 +1,61 @@
+use mlog::*;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+
+use super::shader_compiler::shader_source_dir;
+
+
+// Watches resources/shaders (including subdirectories) for edited shader sources and includes. Events arrive on notify's thread and are drained
+// between frames with changed_files(), so the renderer never reacts in the middle of a frame.
+pub struct ShaderWatcher {
+    _watcher: RecommendedWatcher,  // stops watching when dropped
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+
+        let shaders_path = shader_source_dir();
+        watcher.watch(&shaders_path, RecursiveMode::Recursive)?;
+        info!("Watching {:?} for shader changes", shaders_path);
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    // Files created or modified since the last call, each listed once. Compiled .spv output and the .tmp files
+    // written while saving it or the shader manifest are ignored.
+    // Editors tend to save in several steps (truncate, write, rename), which all collapse into one entry here.
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    crit!("Shader watcher error: {}", e);
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+
+            for path in event.paths {
+                let is_output = path.extension().is_some_and(|ext| ext == "spv" || ext == "tmp");
+                if !is_output && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        changed
+    }
+}
@@ -3,12 +3,23 @@
 use mlog::*;
 
 
-// mod io;
+mod io;
 mod platform;
 // mod renderer;
 
 use openxr as xr;
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use platform::openxr::{
+    DeviceManager, OpenXRInstanceBuilder, OpenXRSession, RemoteControlServer, SessionLifecycle, StereoFrameLoop,
+    DEFAULT_REMOTE_CONTROL_PORT,
+};
+use platform::{RenderConfig, VulkanContext};
+use io::ShaderWatcher;
+
 // use platform::vulkan::context;
 
 // use platform::openxr::{OpenXRSession, ActionSet};
@@ -30,45 +41,171 @@ fn main() {
 
     mlog::init(log_config);
 
-      // Initialize the OpenXR instance
-      let entry = xr::Entry::linked();
-      let instance = entry.create_instance(
-          &xr::ApplicationInfo {
-              application_name: "Neon",
-              application_version: 0,
-              engine_name: "Neon Engine",
-              engine_version: 0,
-          },
-          &[],
-          &[],
-      )?;
-  
-      // Select the Vulkan graphics API
-      let vk_entry = ash::Entry::linked();
-      let vk_instance = create_vulkan_instance(&vk_entry)?;
-  
-      // Create OpenXR system
-      let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
-  
-      // Create session
-      let session = unsafe {
-          instance.create_session(
-              system,
-              &xr::vulkan::SessionCreateInfo {
-                  instance: vk_instance.handle().as_raw() as _,
-                  physical_device: vk::PhysicalDevice::null(), // Replace with actual device
-                  device: vk::Device::null(),                 // Replace with actual device
-                  queue_family_index: 0,                      // Replace with actual queue family index
-                  queue_index: 0,
-              },
-          )?
-      };
+    if std::env::args().any(|arg| arg == "--headless") {
+        run_headless();
+        mlog::shutdown();
+        return;
+    }
+
+    if std::env::args().any(|arg| arg == "--desktop") {
+        run_desktop();
+        mlog::shutdown();
+        return;
+    }
+
+    if let Err(e) = run_xr() {
+        crit!("OpenXR error: {}", e);
+    }
 
     mlog::shutdown();
+}
+
+
+fn run_xr() -> xr::Result<()> {
+    // Initialize the OpenXR instance
+    let entry = xr::Entry::linked();
+    let xr_instance = OpenXRInstanceBuilder::new(&entry)
+        .application("Neon", 0)
+        .require_extension("XR_KHR_vulkan_enable2", |extensions| &mut extensions.khr_vulkan_enable2)
+        .request_extension("XR_EXT_hand_tracking", |extensions| &mut extensions.ext_hand_tracking)
+        .request_extension("XR_KHR_composition_layer_depth", |extensions| &mut extensions.khr_composition_layer_depth)
+        .build()?;
+    let instance = &xr_instance.instance;
+
+    // Create OpenXR system
+    let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
+
+    let mut vk_context = VulkanContext::new(instance, system, RenderConfig::default())?;
+
+    let mut xr_session = OpenXRSession::new(
+        instance,
+        &vk_context.instance,
+        &vk_context.physical_device,
+        &vk_context.device,
+        vk_context.queue_family_index,
+    )?;
+
+    let mut frame_loop = StereoFrameLoop::new(instance, system, &xr_session, &vk_context)?;
+
+    let exit_signal = Arc::new(AtomicBool::new(false));
+    {
+        let exit_signal = exit_signal.clone();
+        ctrlc::set_handler(move || exit_signal.store(true, Ordering::Relaxed))
+            .expect("Failed to set Ctrl-C handler");
+    }
+
+    let shader_watcher = start_shader_watcher();
+
+    let mut lifecycle = SessionLifecycle::new();
+    lifecycle.on_state_changed(|_, new_state| {
+        if new_state == xr::SessionState::FOCUSED {
+            success!("Session focused");
+        }
+    });
+
+    loop {
+        if exit_signal.load(Ordering::Relaxed) {
+            lifecycle.request_exit(&xr_session)?;
+        }
+
+        if !lifecycle.poll_events(instance, &xr_session)? {
+            break;
+        }
+
+        reload_changed_shaders(shader_watcher.as_ref(), &mut vk_context);
+
+        if lifecycle.is_running() {
+            frame_loop.frame(&vk_context, &mut xr_session)?;
+        } else {
+            // Nothing to render until the runtime has moved the session to READY
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    vk_context.allocator.log_statistics();
+
+    // the swapchain and session go before the Vulkan device they were created on
+    drop(frame_loop);
+    drop(xr_session);
+    drop(vk_context);
+    Ok(())
+}
+
+
+// Runs the stereo pipeline without an OpenXR runtime, e.g. in CI on lavapipe.
+fn run_headless() {
+    let vk_context = VulkanContext::new_headless(ash::vk::Extent2D { width: 1280, height: 720 }, RenderConfig::default());
+
+    for frame in 0..10 {
+        vk_context.render_offscreen();
+
+        for view in 0..platform::VIEW_COUNT {
+            let texels = vk_context.read_offscreen_view(view);
+            let checksum = texels.iter().fold(0u64, |acc, &texel| acc.wrapping_mul(31).wrapping_add(texel as u64));
+            info!("frame {} view {} checksum: {:016x}", frame, view, checksum);
+        }
+    }
+
+    vk_context.allocator.log_statistics();
+    success!("Headless run complete");
+}
+
 
+// Keyboard + mouse driven device emulation. The window is only an input surface, frames are rendered by the
+// headless backend and never shown, use --headless to inspect the output.
+fn run_desktop() {
+    let mut vk_context = VulkanContext::new_headless(ash::vk::Extent2D { width: 1280, height: 720 }, RenderConfig::default());
+    let shader_watcher = start_shader_watcher();
 
+    let mut remote_control = start_remote_control();
 
+    let mut device_manager = DeviceManager::new();
+    platform::winit::run_desktop_emulation(&mut device_manager, |device_manager, time| {
+        if let Some(remote_control) = remote_control.as_mut() {
+            remote_control.poll(device_manager, time);
+        }
+        device_manager.update_all_devices(time);
+        reload_changed_shaders(shader_watcher.as_ref(), &mut vk_context);
+        vk_context.render_offscreen();
+    });
+
+    vk_context.allocator.log_statistics();
 }
+
+
+// Shader hot reload is a convenience, running without it is fine.
+fn start_shader_watcher() -> Option<ShaderWatcher> {
+    match ShaderWatcher::new() {
+        Ok(shader_watcher) => Some(shader_watcher),
+        Err(e) => {
+            crit!("Shader hot reload disabled, failed to watch shader directory: {}", e);
+            None
+        }
+    }
+}
+
+
+// Same for the device remote control, e.g. when another instance already has the port.
+fn start_remote_control() -> Option<RemoteControlServer> {
+    match RemoteControlServer::bind_localhost(DEFAULT_REMOTE_CONTROL_PORT) {
+        Ok(remote_control) => Some(remote_control),
+        Err(e) => {
+            crit!("Device remote control disabled, failed to listen on port {}: {}", DEFAULT_REMOTE_CONTROL_PORT, e);
+            None
+        }
+    }
+}
+
+
+// Between frames: picks up edited shader sources and rebuilds what uses them.
+fn reload_changed_shaders(shader_watcher: Option<&ShaderWatcher>, vk_context: &mut VulkanContext) {
+    if let Some(shader_watcher) = shader_watcher {
+        if !shader_watcher.changed_files().is_empty() {
+            vk_context.reload_shaders();
+        }
+    }
+}
+
     // 1. Initialize OpenXR and Vulkan Context
 
 
@@ -1,5 +1,6 @@
 
 pub mod vulkan;
 pub mod openxr;
+pub mod winit;
 pub use vulkan::*;
 pub use openxr::*;
\ No newline at end of file
@@ -0,0 +1,260 @@
+use openxr as xr;
+use serde::Deserialize;
+
+use crate::platform::openxr::default_bindings::{find_interaction_profile, kind_accepts_path, INTERACTION_PROFILES};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Declarative description of action sets, actions and suggested bindings, loaded from TOML or JSON.
+// See resources/actions.toml for the default manifest.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ActionManifest {
+    #[serde(default)]
+    pub action_sets: Vec<ActionSetManifest>,
+    #[serde(default)]
+    pub bindings: Vec<ProfileBindings>,
+    // Add the built-in bindings (see default_bindings.rs) for every profile not listed in `bindings`.
+    #[serde(default = "default_true")]
+    pub use_default_bindings: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActionSetManifest {
+    pub name: String,
+    pub localized_name: String,
+    #[serde(default)]
+    pub priority: u32,
+    #[serde(default)]
+    pub actions: Vec<ActionManifestEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActionManifestEntry {
+    pub name: String,  // unique across all action sets
+    pub localized_name: String,
+    #[serde(rename = "type")]
+    pub kind: ActionKind,
+    #[serde(default)]
+    pub subaction_paths: Vec<String>,  // e.g. "/user/hand/left"
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionKind {
+    Boolean,
+    Float,
+    Vector2f,
+    Pose,
+    Haptic,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProfileBindings {
+    pub interaction_profile: String,  // e.g. "/interaction_profiles/khr/simple_controller"
+    #[serde(default)]
+    pub suggested: Vec<SuggestedBinding>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SuggestedBinding {
+    pub action: String,
+    pub path: String,  // e.g. "/user/hand/right/input/grip/pose"
+}
+
+
+#[derive(Debug)]
+pub enum ActionManifestError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    DuplicateAction(String),
+    UnknownAction { interaction_profile: String, action: String },
+    InvalidBindingPath { interaction_profile: String, path: String },
+    BindingTypeMismatch { interaction_profile: String, action: String, kind: ActionKind, path: String },
+    Xr(xr::sys::Result),
+}
+
+impl fmt::Display for ActionManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionManifestError::Io(path, e) => write!(f, "failed to read action manifest {:?}: {}", path, e),
+            ActionManifestError::Parse(path, e) => write!(f, "failed to parse action manifest {:?}: {}", path, e),
+            ActionManifestError::DuplicateAction(name) => write!(f, "action '{}' is declared more than once", name),
+            ActionManifestError::UnknownAction { interaction_profile, action } => {
+                write!(f, "binding for {} references unknown action '{}'", interaction_profile, action)
+            }
+            ActionManifestError::InvalidBindingPath { interaction_profile, path } => {
+                write!(f, "{} is not a valid input path for {}", path, interaction_profile)
+            }
+            ActionManifestError::BindingTypeMismatch { interaction_profile, action, kind, path } => write!(
+                f,
+                "{:?} action '{}' cannot be bound to {} in {}",
+                kind, action, path, interaction_profile
+            ),
+            ActionManifestError::Xr(e) => write!(f, "OpenXR error while creating actions: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ActionManifestError {}
+
+impl From<xr::sys::Result> for ActionManifestError {
+    fn from(e: xr::sys::Result) -> Self {
+        ActionManifestError::Xr(e)
+    }
+}
+
+
+impl ActionManifest {
+    pub fn default_path() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join("actions.toml")
+    }
+
+    // Parses TOML, or JSON when the file has a .json extension.
+    pub fn load(path: &Path) -> Result<Self, ActionManifestError> {
+        let source = fs::read_to_string(path).map_err(|e| ActionManifestError::Io(path.to_path_buf(), e))?;
+
+        let manifest = if path.extension().map_or(false, |extension| extension == "json") {
+            Self::from_json(&source)
+        } else {
+            Self::from_toml(&source)
+        }
+        .map_err(|e| ActionManifestError::Parse(path.to_path_buf(), e))?;
+
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        toml::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, String> {
+        serde_json::from_str(source).map_err(|e| e.to_string())
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &ActionManifestEntry> {
+        self.action_sets.iter().flat_map(|action_set| action_set.actions.iter())
+    }
+
+    pub fn action(&self, name: &str) -> Option<&ActionManifestEntry> {
+        self.actions().find(|action| action.name == name)
+    }
+
+    // The manifest's own bindings followed by the built-in ones for the remaining profiles, restricted to
+    // actions the manifest declares with a compatible type.
+    pub fn resolved_bindings(&self) -> Vec<ProfileBindings> {
+        let mut bindings = self.bindings.clone();
+        if !self.use_default_bindings {
+            return bindings;
+        }
+
+        for profile in INTERACTION_PROFILES {
+            if self.bindings.iter().any(|listed| listed.interaction_profile == profile.path) {
+                continue;
+            }
+
+            let mut defaults = profile.default_bindings();
+            defaults.suggested.retain(|binding| {
+                self.action(&binding.action)
+                    .map_or(false, |action| kind_accepts_path(action.kind, &binding.path))
+            });
+
+            if !defaults.suggested.is_empty() {
+                bindings.push(defaults);
+            }
+        }
+        bindings
+    }
+
+    // Catches mistakes that OpenXR would only report as a generic failure. Paths are only checked for profiles
+    // in INTERACTION_PROFILES, others (vendor extensions we don't know about) are left to the runtime.
+    pub fn validate(&self) -> Result<(), ActionManifestError> {
+        let mut kinds = HashMap::new();
+        for action in self.actions() {
+            if kinds.insert(action.name.as_str(), action.kind).is_some() {
+                return Err(ActionManifestError::DuplicateAction(action.name.clone()));
+            }
+        }
+
+        for profile in self.resolved_bindings() {
+            let known_profile = find_interaction_profile(&profile.interaction_profile);
+
+            for binding in &profile.suggested {
+                let kind = *kinds.get(binding.action.as_str()).ok_or_else(|| ActionManifestError::UnknownAction {
+                    interaction_profile: profile.interaction_profile.clone(),
+                    action: binding.action.clone(),
+                })?;
+
+                let Some(known_profile) = known_profile else { continue };
+
+                if !known_profile.allows(&binding.path) {
+                    return Err(ActionManifestError::InvalidBindingPath {
+                        interaction_profile: profile.interaction_profile.clone(),
+                        path: binding.path.clone(),
+                    });
+                }
+
+                if !kind_accepts_path(kind, &binding.path) {
+                    return Err(ActionManifestError::BindingTypeMismatch {
+                        interaction_profile: profile.interaction_profile.clone(),
+                        action: binding.action.clone(),
+                        kind,
+                        path: binding.path.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(profile: &str, action: &str, path: &str) -> ActionManifest {
+        ActionManifest::from_toml(&format!(
+            r#"
+            use_default_bindings = false
+
+            [[action_sets]]
+            name = "gameplay"
+            localized_name = "Gameplay"
+            actions = [{{ name = "select", localized_name = "Select", type = "boolean" }}]
+
+            [[bindings]]
+            interaction_profile = "{}"
+            suggested = [{{ action = "{}", path = "{}" }}]
+            "#,
+            profile, action, path
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn unknown_profiles_are_passed_through() {
+        let manifest = manifest("/interaction_profiles/acme/prototype_controller", "select", "/user/hand/right/input/anything/click");
+        assert!(manifest.validate().is_ok());
+        assert_eq!(manifest.resolved_bindings().len(), 1);
+    }
+
+    #[test]
+    fn unknown_profiles_still_need_declared_actions() {
+        let manifest = manifest("/interaction_profiles/acme/prototype_controller", "jump", "/user/hand/right/input/a/click");
+        assert!(matches!(manifest.validate(), Err(ActionManifestError::UnknownAction { .. })));
+    }
+
+    #[test]
+    fn known_profiles_reject_invalid_paths() {
+        let manifest = manifest("/interaction_profiles/khr/simple_controller", "select", "/user/hand/right/input/anything/click");
+        assert!(matches!(manifest.validate(), Err(ActionManifestError::InvalidBindingPath { .. })));
+    }
+}
@@ -1,47 +1,252 @@
 use openxr as xr;
+use mlog::*;
 
+use std::collections::HashMap;
+
+use crate::platform::openxr::action_manifest::{ActionKind, ActionManifest, ActionManifestError};
+use crate::platform::openxr::default_bindings::{find_interaction_profile, Hand, GRIP_POSE_ACTION};
+
+pub enum AnyAction {
+    Boolean(xr::Action<bool>),
+    Float(xr::Action<f32>),
+    Vector2f(xr::Action<xr::Vector2f>),
+    Pose(xr::Action<xr::Posef>),
+    Haptic(xr::Action<xr::Haptic>),
+}
+
+impl AnyAction {
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            AnyAction::Boolean(_) => ActionKind::Boolean,
+            AnyAction::Float(_) => ActionKind::Float,
+            AnyAction::Vector2f(_) => ActionKind::Vector2f,
+            AnyAction::Pose(_) => ActionKind::Pose,
+            AnyAction::Haptic(_) => ActionKind::Haptic,
+        }
+    }
+
+    fn binding(&self, path: xr::Path) -> xr::Binding<'_> {
+        match self {
+            AnyAction::Boolean(action) => xr::Binding::new(action, path),
+            AnyAction::Float(action) => xr::Binding::new(action, path),
+            AnyAction::Vector2f(action) => xr::Binding::new(action, path),
+            AnyAction::Pose(action) => xr::Binding::new(action, path),
+            AnyAction::Haptic(action) => xr::Binding::new(action, path),
+        }
+    }
+}
+
+pub struct ActionEntry {
+    pub action: AnyAction,
+    pub subaction_paths: Vec<xr::Path>,
+}
+
+// All action sets and actions declared by an ActionManifest, looked up by action name.
 pub struct ActionSet {
-    pub action_set: xr::ActionSet,
-    pub right_hand_action: xr::Action<xr::Posef>,
-    pub left_hand_action: xr::Action<xr::Posef>,
+    pub action_sets: Vec<xr::ActionSet>,
+    actions: HashMap<String, ActionEntry>,
+    hand_paths: [xr::Path; 2],  // indexed by Hand
+    pose_spaces: HashMap<String, Vec<(xr::Path, xr::Space)>>,  // created in `attach`, one per subaction path
 }
 
 impl ActionSet {
-    pub fn new(xr_instance: &xr::Instance) -> xr::Result<Self> {
-        // Create an action set
-        let action_set = xr_instance.create_action_set("input", "Input Pose Information", 0)?;
+    // Loads resources/actions.toml.
+    pub fn new(xr_instance: &xr::Instance) -> Result<Self, ActionManifestError> {
+        let manifest = ActionManifest::load(&ActionManifest::default_path())?;
+        Self::from_manifest(xr_instance, &manifest)
+    }
+
+    pub fn from_manifest(xr_instance: &xr::Instance, manifest: &ActionManifest) -> Result<Self, ActionManifestError> {
+        manifest.validate()?;
 
-        // Create actions
-        let right_hand_action = action_set.create_action::<xr::Posef>("right_hand", "Right Hand Controller", &[])?;
-        let left_hand_action = action_set.create_action::<xr::Posef>("left_hand", "Left Hand Controller", &[])?;
+        let mut action_sets = Vec::new();
+        let mut actions = HashMap::new();
 
-        // Suggest interaction profile bindings
-        xr_instance.suggest_interaction_profile_bindings(
-            xr_instance.string_to_path("/interaction_profiles/khr/simple_controller")?,
-            &[
-                xr::Binding::new(&right_hand_action, xr_instance.string_to_path("/user/hand/right/input/grip/pose")?),
-                xr::Binding::new(&left_hand_action, xr_instance.string_to_path("/user/hand/left/input/grip/pose")?),
-            ],
-        )?;
+        for set_manifest in &manifest.action_sets {
+            let action_set = xr_instance.create_action_set(&set_manifest.name, &set_manifest.localized_name, set_manifest.priority)?;
 
-        Ok(Self {
-            action_set,
-            right_hand_action,
-            left_hand_action,
-        })
+            for action_manifest in &set_manifest.actions {
+                let subaction_paths = action_manifest
+                    .subaction_paths
+                    .iter()
+                    .map(|path| xr_instance.string_to_path(path))
+                    .collect::<xr::Result<Vec<_>>>()?;
+
+                let name = action_manifest.name.as_str();
+                let localized_name = action_manifest.localized_name.as_str();
+                let action = match action_manifest.kind {
+                    ActionKind::Boolean => AnyAction::Boolean(action_set.create_action(name, localized_name, &subaction_paths)?),
+                    ActionKind::Float => AnyAction::Float(action_set.create_action(name, localized_name, &subaction_paths)?),
+                    ActionKind::Vector2f => AnyAction::Vector2f(action_set.create_action(name, localized_name, &subaction_paths)?),
+                    ActionKind::Pose => AnyAction::Pose(action_set.create_action(name, localized_name, &subaction_paths)?),
+                    ActionKind::Haptic => AnyAction::Haptic(action_set.create_action(name, localized_name, &subaction_paths)?),
+                };
+
+                actions.insert(action_manifest.name.clone(), ActionEntry { action, subaction_paths });
+            }
+
+            action_sets.push(action_set);
+        }
+
+        let hand_paths = [
+            xr_instance.string_to_path(Hand::Left.user_path())?,
+            xr_instance.string_to_path(Hand::Right.user_path())?,
+        ];
+
+        let action_set = Self {
+            action_sets,
+            actions,
+            hand_paths,
+            pose_spaces: HashMap::new(),
+        };
+
+        for profile in manifest.resolved_bindings() {
+            let bindings = profile
+                .suggested
+                .iter()
+                .map(|binding| {
+                    let path = xr_instance.string_to_path(&binding.path)?;
+                    Ok(action_set.actions[&binding.action].action.binding(path))
+                })
+                .collect::<xr::Result<Vec<_>>>()?;
+
+            // unknown profiles (e.g. from vendor extensions) passed validation untouched, the runtime has the last word
+            let known_profile = find_interaction_profile(&profile.interaction_profile);
+            if known_profile.is_none() {
+                crit!("Suggesting bindings for unknown interaction profile {} without validating them", profile.interaction_profile);
+            }
+
+            let result = xr_instance.suggest_interaction_profile_bindings(
+                xr_instance.string_to_path(&profile.interaction_profile)?,
+                &bindings,
+            );
+
+            // profiles provided by an extension are rejected with PATH_UNSUPPORTED when it is not enabled
+            let extension = known_profile.map(|known| known.extension);
+            match (result, extension) {
+                (Ok(()), _) => info!("Suggested {} bindings for {}", bindings.len(), profile.interaction_profile),
+                (Err(xr::sys::Result::ERROR_PATH_UNSUPPORTED), Some(Some(extension))) => {
+                    info!("Skipping bindings for {}, {} is not enabled", profile.interaction_profile, extension)
+                }
+                (Err(xr::sys::Result::ERROR_PATH_UNSUPPORTED), None) => {
+                    crit!("Skipping bindings for {}, the runtime does not support it", profile.interaction_profile)
+                }
+                (Err(e), _) => return Err(e.into()),
+            }
+        }
+
+        Ok(action_set)
     }
 
-    pub fn attach(&self, session: &xr::Session<xr::Vulkan>) -> xr::Result<()> {
-        session.attach_action_sets(&[&self.action_set])
+    pub fn hand_path(&self, hand: Hand) -> xr::Path {
+        self.hand_paths[hand as usize]
     }
 
+    pub fn entry(&self, name: &str) -> Option<&ActionEntry> {
+        self.actions.get(name)
+    }
+
+    pub fn boolean(&self, name: &str) -> Option<&xr::Action<bool>> {
+        match &self.entry(name)?.action {
+            AnyAction::Boolean(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, name: &str) -> Option<&xr::Action<f32>> {
+        match &self.entry(name)?.action {
+            AnyAction::Float(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    pub fn vector2f(&self, name: &str) -> Option<&xr::Action<xr::Vector2f>> {
+        match &self.entry(name)?.action {
+            AnyAction::Vector2f(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    pub fn pose(&self, name: &str) -> Option<&xr::Action<xr::Posef>> {
+        match &self.entry(name)?.action {
+            AnyAction::Pose(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    pub fn haptic(&self, name: &str) -> Option<&xr::Action<xr::Haptic>> {
+        match &self.entry(name)?.action {
+            AnyAction::Haptic(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    // Why a typed lookup like `pose` returned None: PATH_UNSUPPORTED if the manifest doesn't declare `name`,
+    // ACTION_TYPE_MISMATCH if it is declared with another type.
+    pub fn lookup_error(&self, name: &str) -> xr::sys::Result {
+        match self.entry(name) {
+            Some(_) => xr::sys::Result::ERROR_ACTION_TYPE_MISMATCH,
+            None => xr::sys::Result::ERROR_PATH_UNSUPPORTED,
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &ActionEntry)> {
+        self.actions.iter().map(|(name, entry)| (name.as_str(), entry))
+    }
+
+    // Also creates a space for every pose action and subaction path, used by `sync_and_snapshot`.
+    pub fn attach(&mut self, session: &xr::Session<xr::Vulkan>) -> xr::Result<()> {
+        session.attach_action_sets(&self.action_sets.iter().collect::<Vec<_>>())?;
+
+        for (name, entry) in &self.actions {
+            let AnyAction::Pose(action) = &entry.action else {
+                continue;
+            };
+
+            let subaction_paths = if entry.subaction_paths.is_empty() {
+                vec![xr::Path::NULL]
+            } else {
+                entry.subaction_paths.clone()
+            };
+
+            let spaces = subaction_paths
+                .into_iter()
+                .map(|path| Ok((path, action.create_space(session.clone(), path, xr::Posef::IDENTITY)?)))
+                .collect::<xr::Result<Vec<_>>>()?;
+            self.pose_spaces.insert(name.clone(), spaces);
+        }
+        Ok(())
+    }
+
+    pub fn pose_space(&self, name: &str, subaction_path: xr::Path) -> Option<&xr::Space> {
+        self.pose_spaces
+            .get(name)?
+            .iter()
+            .find(|(path, _)| *path == subaction_path)
+            .map(|(_, space)| space)
+    }
+
+    pub fn create_action_space(
+        &self,
+        session: &xr::Session<xr::Vulkan>,
+        name: &str,
+        subaction_path: xr::Path,
+    ) -> xr::Result<xr::Space> {
+        let action = self.pose(name).ok_or_else(|| {
+            crit!("Cannot create a space for '{}', the action manifest declares no pose action by that name", name);
+            self.lookup_error(name)
+        })?;
+        action.create_space(session.clone(), subaction_path, xr::Posef::IDENTITY)
+    }
+
+    // Grip spaces of the right and left hand.
     pub fn create_action_spaces(
         &self,
         session: &xr::Session<xr::Vulkan>,
     ) -> xr::Result<(xr::Space, xr::Space)> {
-        let right_space = self.right_hand_action.create_space(session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)?;
-        let left_space = self.left_hand_action.create_space(session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)?;
+        let right_space = self.create_action_space(session, GRIP_POSE_ACTION, self.hand_path(Hand::Right))?;
+        let left_space = self.create_action_space(session, GRIP_POSE_ACTION, self.hand_path(Hand::Left))?;
 
         Ok((right_space, left_space))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,331 @@
+use crate::platform::openxr::action_manifest::{ActionKind, ProfileBindings, SuggestedBinding};
+use crate::platform::openxr::device_emulation::{LEFT_HAND_DEVICE, RIGHT_HAND_DEVICE};
+
+pub const LEFT_HAND_PATH: &str = "/user/hand/left";
+pub const RIGHT_HAND_PATH: &str = "/user/hand/right";
+
+// Semantic action names the built-in bindings refer to. A manifest only needs to declare the ones it uses.
+pub const SELECT_ACTION: &str = "select";
+pub const GRAB_ACTION: &str = "grab";
+pub const MENU_ACTION: &str = "menu";
+pub const THUMBSTICK_ACTION: &str = "thumbstick";
+pub const TRACKPAD_ACTION: &str = "trackpad";
+pub const AIM_POSE_ACTION: &str = "aim_pose";
+pub const GRIP_POSE_ACTION: &str = "grip_pose";
+pub const HAPTIC_ACTION: &str = "haptic";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    pub const ALL: [Hand; 2] = [Hand::Left, Hand::Right];
+
+    pub fn user_path(&self) -> &'static str {
+        match self {
+            Hand::Left => LEFT_HAND_PATH,
+            Hand::Right => RIGHT_HAND_PATH,
+        }
+    }
+
+    // Name of the matching VirtualDevice in emulation.
+    pub fn device_name(&self) -> &'static str {
+        match self {
+            Hand::Left => LEFT_HAND_DEVICE,
+            Hand::Right => RIGHT_HAND_DEVICE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hands {
+    Both,
+    Left,
+    Right,
+}
+
+impl Hands {
+    pub fn user_paths(&self) -> &'static [&'static str] {
+        match self {
+            Hands::Both => &[LEFT_HAND_PATH, RIGHT_HAND_PATH],
+            Hands::Left => &[LEFT_HAND_PATH],
+            Hands::Right => &[RIGHT_HAND_PATH],
+        }
+    }
+}
+
+// An interaction profile with the component paths (relative to /user/hand/<side>/) it allows,
+// and the components our semantic actions are bound to by default.
+pub struct InteractionProfile {
+    pub path: &'static str,
+    pub extension: Option<&'static str>,  // profiles added by an extension are only accepted when it is enabled
+    pub components: &'static [(&'static str, Hands)],
+    pub defaults: &'static [(&'static str, &'static str, Hands)],  // (action, component, hands)
+}
+
+impl InteractionProfile {
+    pub fn allows(&self, path: &str) -> bool {
+        self.components.iter().any(|(component, hands)| {
+            hands.user_paths().iter().any(|user_path| {
+                path.strip_prefix(user_path)
+                    .and_then(|rest| rest.strip_prefix('/'))
+                    .map_or(false, |rest| rest == *component)
+            })
+        })
+    }
+
+    pub fn default_bindings(&self) -> ProfileBindings {
+        let mut suggested = Vec::new();
+        for (action, component, hands) in self.defaults {
+            for user_path in hands.user_paths() {
+                suggested.push(SuggestedBinding {
+                    action: action.to_string(),
+                    path: format!("{}/{}", user_path, component),
+                });
+            }
+        }
+
+        ProfileBindings {
+            interaction_profile: self.path.to_string(),
+            suggested,
+        }
+    }
+}
+
+pub fn find_interaction_profile(path: &str) -> Option<&'static InteractionProfile> {
+    INTERACTION_PROFILES.iter().find(|profile| profile.path == path)
+}
+
+// Whether an action of `kind` can be bound to a component path, OpenXR itself only reports ERROR_PATH_UNSUPPORTED
+// for the whole profile. Boolean and float actions may be bound to any scalar input, the runtime converts.
+pub fn kind_accepts_path(kind: ActionKind, path: &str) -> bool {
+    let is_pose = path.ends_with("/pose");
+    let is_output = path.contains("/output/");
+    let is_vector2 = path.ends_with("/thumbstick") || path.ends_with("/trackpad");
+
+    match kind {
+        ActionKind::Pose => is_pose,
+        ActionKind::Haptic => is_output,
+        ActionKind::Vector2f => is_vector2,
+        ActionKind::Boolean | ActionKind::Float => !is_pose && !is_output && !is_vector2,
+    }
+}
+
+
+pub static INTERACTION_PROFILES: &[InteractionProfile] = &[
+    InteractionProfile {
+        path: "/interaction_profiles/khr/simple_controller",
+        extension: None,
+        components: &[
+            ("input/select/click", Hands::Both),
+            ("input/menu/click", Hands::Both),
+            ("input/grip/pose", Hands::Both),
+            ("input/aim/pose", Hands::Both),
+            ("output/haptic", Hands::Both),
+        ],
+        defaults: &[
+            (SELECT_ACTION, "input/select/click", Hands::Both),
+            (MENU_ACTION, "input/menu/click", Hands::Both),
+            (GRIP_POSE_ACTION, "input/grip/pose", Hands::Both),
+            (AIM_POSE_ACTION, "input/aim/pose", Hands::Both),
+            (HAPTIC_ACTION, "output/haptic", Hands::Both),
+        ],
+    },
+    InteractionProfile {
+        path: "/interaction_profiles/oculus/touch_controller",
+        extension: None,
+        components: &[
+            ("input/x/click", Hands::Left),
+            ("input/x/touch", Hands::Left),
+            ("input/y/click", Hands::Left),
+            ("input/y/touch", Hands::Left),
+            ("input/menu/click", Hands::Left),
+            ("input/a/click", Hands::Right),
+            ("input/a/touch", Hands::Right),
+            ("input/b/click", Hands::Right),
+            ("input/b/touch", Hands::Right),
+            ("input/system/click", Hands::Right),
+            ("input/squeeze/value", Hands::Both),
+            ("input/trigger/value", Hands::Both),
+            ("input/trigger/touch", Hands::Both),
+            ("input/thumbstick", Hands::Both),
+            ("input/thumbstick/x", Hands::Both),
+            ("input/thumbstick/y", Hands::Both),
+            ("input/thumbstick/click", Hands::Both),
+            ("input/thumbstick/touch", Hands::Both),
+            ("input/thumbrest/touch", Hands::Both),
+            ("input/grip/pose", Hands::Both),
+            ("input/aim/pose", Hands::Both),
+            ("output/haptic", Hands::Both),
+        ],
+        defaults: &[
+            (SELECT_ACTION, "input/trigger/value", Hands::Both),
+            (GRAB_ACTION, "input/squeeze/value", Hands::Both),
+            (MENU_ACTION, "input/menu/click", Hands::Left),
+            (MENU_ACTION, "input/b/click", Hands::Right),
+            (THUMBSTICK_ACTION, "input/thumbstick", Hands::Both),
+            (GRIP_POSE_ACTION, "input/grip/pose", Hands::Both),
+            (AIM_POSE_ACTION, "input/aim/pose", Hands::Both),
+            (HAPTIC_ACTION, "output/haptic", Hands::Both),
+        ],
+    },
+    InteractionProfile {
+        path: "/interaction_profiles/valve/index_controller",
+        extension: None,
+        components: &[
+            ("input/system/click", Hands::Both),
+            ("input/system/touch", Hands::Both),
+            ("input/a/click", Hands::Both),
+            ("input/a/touch", Hands::Both),
+            ("input/b/click", Hands::Both),
+            ("input/b/touch", Hands::Both),
+            ("input/squeeze/value", Hands::Both),
+            ("input/squeeze/force", Hands::Both),
+            ("input/trigger/click", Hands::Both),
+            ("input/trigger/value", Hands::Both),
+            ("input/trigger/touch", Hands::Both),
+            ("input/thumbstick", Hands::Both),
+            ("input/thumbstick/x", Hands::Both),
+            ("input/thumbstick/y", Hands::Both),
+            ("input/thumbstick/click", Hands::Both),
+            ("input/thumbstick/touch", Hands::Both),
+            ("input/trackpad", Hands::Both),
+            ("input/trackpad/x", Hands::Both),
+            ("input/trackpad/y", Hands::Both),
+            ("input/trackpad/force", Hands::Both),
+            ("input/trackpad/touch", Hands::Both),
+            ("input/grip/pose", Hands::Both),
+            ("input/aim/pose", Hands::Both),
+            ("output/haptic", Hands::Both),
+        ],
+        defaults: &[
+            (SELECT_ACTION, "input/trigger/click", Hands::Both),
+            (GRAB_ACTION, "input/squeeze/value", Hands::Both),
+            (MENU_ACTION, "input/b/click", Hands::Both),
+            (THUMBSTICK_ACTION, "input/thumbstick", Hands::Both),
+            (TRACKPAD_ACTION, "input/trackpad", Hands::Both),
+            (GRIP_POSE_ACTION, "input/grip/pose", Hands::Both),
+            (AIM_POSE_ACTION, "input/aim/pose", Hands::Both),
+            (HAPTIC_ACTION, "output/haptic", Hands::Both),
+        ],
+    },
+    InteractionProfile {
+        path: "/interaction_profiles/htc/vive_controller",
+        extension: None,
+        components: &[
+            ("input/system/click", Hands::Both),
+            ("input/squeeze/click", Hands::Both),
+            ("input/menu/click", Hands::Both),
+            ("input/trigger/click", Hands::Both),
+            ("input/trigger/value", Hands::Both),
+            ("input/trackpad", Hands::Both),
+            ("input/trackpad/x", Hands::Both),
+            ("input/trackpad/y", Hands::Both),
+            ("input/trackpad/click", Hands::Both),
+            ("input/trackpad/touch", Hands::Both),
+            ("input/grip/pose", Hands::Both),
+            ("input/aim/pose", Hands::Both),
+            ("output/haptic", Hands::Both),
+        ],
+        defaults: &[
+            (SELECT_ACTION, "input/trigger/click", Hands::Both),
+            (GRAB_ACTION, "input/squeeze/click", Hands::Both),
+            (MENU_ACTION, "input/menu/click", Hands::Both),
+            (TRACKPAD_ACTION, "input/trackpad", Hands::Both),
+            (GRIP_POSE_ACTION, "input/grip/pose", Hands::Both),
+            (AIM_POSE_ACTION, "input/aim/pose", Hands::Both),
+            (HAPTIC_ACTION, "output/haptic", Hands::Both),
+        ],
+    },
+    InteractionProfile {
+        path: "/interaction_profiles/microsoft/motion_controller",
+        extension: None,
+        components: &[
+            ("input/menu/click", Hands::Both),
+            ("input/squeeze/click", Hands::Both),
+            ("input/trigger/value", Hands::Both),
+            ("input/thumbstick", Hands::Both),
+            ("input/thumbstick/x", Hands::Both),
+            ("input/thumbstick/y", Hands::Both),
+            ("input/thumbstick/click", Hands::Both),
+            ("input/trackpad", Hands::Both),
+            ("input/trackpad/x", Hands::Both),
+            ("input/trackpad/y", Hands::Both),
+            ("input/trackpad/click", Hands::Both),
+            ("input/trackpad/touch", Hands::Both),
+            ("input/grip/pose", Hands::Both),
+            ("input/aim/pose", Hands::Both),
+            ("output/haptic", Hands::Both),
+        ],
+        defaults: &[
+            (SELECT_ACTION, "input/trigger/value", Hands::Both),
+            (GRAB_ACTION, "input/squeeze/click", Hands::Both),
+            (MENU_ACTION, "input/menu/click", Hands::Both),
+            (THUMBSTICK_ACTION, "input/thumbstick", Hands::Both),
+            (TRACKPAD_ACTION, "input/trackpad", Hands::Both),
+            (GRIP_POSE_ACTION, "input/grip/pose", Hands::Both),
+            (AIM_POSE_ACTION, "input/aim/pose", Hands::Both),
+            (HAPTIC_ACTION, "output/haptic", Hands::Both),
+        ],
+    },
+    InteractionProfile {
+        path: "/interaction_profiles/ext/hand_interaction_ext",
+        extension: Some("XR_EXT_hand_interaction"),
+        components: &[
+            ("input/aim/pose", Hands::Both),
+            ("input/grip/pose", Hands::Both),
+            ("input/pinch_ext/pose", Hands::Both),
+            ("input/poke_ext/pose", Hands::Both),
+            ("input/pinch_ext/value", Hands::Both),
+            ("input/pinch_ext/ready_ext", Hands::Both),
+            ("input/aim_activate_ext/value", Hands::Both),
+            ("input/aim_activate_ext/ready_ext", Hands::Both),
+            ("input/grasp_ext/value", Hands::Both),
+            ("input/grasp_ext/ready_ext", Hands::Both),
+        ],
+        defaults: &[
+            (SELECT_ACTION, "input/pinch_ext/value", Hands::Both),
+            (GRAB_ACTION, "input/grasp_ext/value", Hands::Both),
+            (GRIP_POSE_ACTION, "input/grip/pose", Hands::Both),
+            (AIM_POSE_ACTION, "input/aim/pose", Hands::Both),
+        ],
+    },
+];
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The action types resources/actions.toml declares for the semantic actions.
+    fn semantic_action_kind(action: &str) -> ActionKind {
+        match action {
+            SELECT_ACTION | GRAB_ACTION | MENU_ACTION => ActionKind::Boolean,
+            THUMBSTICK_ACTION | TRACKPAD_ACTION => ActionKind::Vector2f,
+            AIM_POSE_ACTION | GRIP_POSE_ACTION => ActionKind::Pose,
+            HAPTIC_ACTION => ActionKind::Haptic,
+            other => panic!("default binding for unknown action '{}'", other),
+        }
+    }
+
+    #[test]
+    fn default_bindings_are_valid_for_their_profile_and_action() {
+        for profile in INTERACTION_PROFILES {
+            for (action, component, hands) in profile.defaults {
+                for user_path in hands.user_paths() {
+                    let path = format!("{}/{}", user_path, component);
+                    assert!(profile.allows(&path), "{} doesn't allow {}", profile.path, path);
+                    assert!(
+                        kind_accepts_path(semantic_action_kind(action), &path),
+                        "{} binds {} to {}, which doesn't accept its kind",
+                        profile.path,
+                        action,
+                        path
+                    );
+                }
+            }
+        }
+    }
+}
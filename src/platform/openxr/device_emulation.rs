@@ -1,37 +1,114 @@
 use openxr as xr;
 use mlog::*;
 
-use crate::platform::openxr::session::OpenXRSession;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::platform::openxr::default_bindings::Hand;
+use crate::platform::openxr::hand_emulation::{generate_hand_skeleton, HandGesture};
+use crate::platform::openxr::hand_tracking::HandSkeleton;
+use crate::platform::openxr::haptics::HapticEvent;
+use crate::platform::openxr::motion::MotionSource;
+use crate::platform::openxr::pose_recording::{PoseRecorder, PoseRecording, PoseReplay};
+
+// Names of the devices the desktop emulation and the default bindings drive.
+pub const HEAD_DEVICE: &str = "head";
+pub const LEFT_HAND_DEVICE: &str = "left_hand";
+pub const RIGHT_HAND_DEVICE: &str = "right_hand";
 
 pub struct VirtualDevice {
     pub name: String,
-    pub space: xr::Space,
+    pub space: Option<xr::Space>,  // None for devices that only exist in emulation (e.g. created by a replay)
+    pub pose: xr::Posef,
+    pub motion: Option<Box<dyn MotionSource>>,  // drives `pose` in DeviceManager::update_all_devices
+    pub buttons: HashMap<String, bool>,  // e.g. "trigger", "grip", "menu"
+    pub axes: HashMap<String, f32>,  // e.g. "trigger", "thumbstick_x"
+    pub hand_gesture: Option<HandGesture>,  // emulated hand tracking, `pose` is used as the wrist
+    pub remote_controlled: bool,  // pose set over RemoteControlServer, local desktop input leaves it alone
+    haptic_events: Vec<HapticEvent>,  // everything sent through HapticOutput, oldest first
 }
 
 impl VirtualDevice {
     pub fn new(name: &str, space: xr::Space) -> Self {
         Self {
             name: name.to_string(),
-            space,
+            space: Some(space),
+            pose: xr::Posef::IDENTITY,
+            motion: None,
+            buttons: HashMap::new(),
+            axes: HashMap::new(),
+            hand_gesture: None,
+            remote_controlled: false,
+            haptic_events: Vec::new(),
         }
     }
 
-    pub fn update_pose(&self, session: &OpenXRSession, pose: xr::Posef) -> xr::Result<()> {
-        // Here you would normally update the pose for the device
-        mlog::info!("Updating pose for device: {} to {:?}", self.name, pose);
+    // Device without an OpenXR space, usable without a running session.
+    pub fn detached(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            space: None,
+            pose: xr::Posef::IDENTITY,
+            motion: None,
+            buttons: HashMap::new(),
+            axes: HashMap::new(),
+            hand_gesture: None,
+            remote_controlled: false,
+            haptic_events: Vec::new(),
+        }
+    }
 
+    pub fn update_pose(&mut self, pose: xr::Posef) -> xr::Result<()> {
+        self.pose = pose;
         Ok(())
     }
+
+    pub fn set_button(&mut self, button: &str, pressed: bool) {
+        self.buttons.insert(button.to_string(), pressed);
+    }
+
+    pub fn button(&self, button: &str) -> bool {
+        self.buttons.get(button).copied().unwrap_or(false)
+    }
+
+    pub fn set_axis(&mut self, axis: &str, value: f32) {
+        self.axes.insert(axis.to_string(), value);
+    }
+
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.axes.get(axis).copied().unwrap_or(0.0)
+    }
+
+    pub fn hand_skeleton(&self, hand: Hand) -> Option<HandSkeleton> {
+        self.hand_gesture.map(|gesture| generate_hand_skeleton(hand, self.pose, gesture))
+    }
+
+    pub fn push_haptic_event(&mut self, event: HapticEvent) {
+        self.haptic_events.push(event);
+    }
+
+    pub fn haptic_events(&self) -> &[HapticEvent] {
+        &self.haptic_events
+    }
+
+    pub fn take_haptic_events(&mut self) -> Vec<HapticEvent> {
+        std::mem::take(&mut self.haptic_events)
+    }
 }
 
 pub struct DeviceManager {
     pub devices: Vec<VirtualDevice>,
+    recorder: Option<PoseRecorder>,
+    replay: Option<PoseReplay>,
 }
 
 impl DeviceManager {
     pub fn new() -> Self {
         Self {
             devices: Vec::new(),
+            recorder: None,
+            replay: None,
         }
     }
 
@@ -39,24 +116,210 @@ impl DeviceManager {
         self.devices.push(device);
     }
 
-    pub fn update_all_devices(&self, session: &OpenXRSession) {
-        for device in &self.devices {
-            // Here we would update the poses based on some logic or input data
-            // For example, you could call device.update_pose(session, new_pose);
+    pub fn device(&self, device_name: &str) -> Option<&VirtualDevice> {
+        self.devices.iter().find(|device| device.name == device_name)
+    }
+
+    pub fn device_mut(&mut self, device_name: &str) -> Option<&mut VirtualDevice> {
+        self.devices.iter_mut().find(|device| device.name == device_name)
+    }
+
+    // Returns the named device, adding a detached one first if it doesn't exist yet.
+    pub fn ensure_device(&mut self, device_name: &str) -> &mut VirtualDevice {
+        if self.device(device_name).is_none() {
+            self.add_device(VirtualDevice::detached(device_name));
         }
+        self.device_mut(device_name).unwrap()
+    }
+
+    pub fn set_motion_source(&mut self, device_name: &str, motion: Box<dyn MotionSource>) -> xr::Result<()> {
+        let device = self
+            .device_mut(device_name)
+            .ok_or(xr::sys::Result::ERROR_HANDLE_INVALID)?;
+        device.motion = Some(motion);
+        Ok(())
+    }
+
+    pub fn set_hand_gesture(&mut self, hand: Hand, gesture: Option<HandGesture>) {
+        self.ensure_device(hand.device_name()).hand_gesture = gesture;
+    }
+
+    // Emulated counterpart of HandTracking::locate, None unless a gesture was set for the hand.
+    pub fn hand_skeleton(&self, hand: Hand) -> Option<HandSkeleton> {
+        self.device(hand.device_name())?.hand_skeleton(hand)
     }
 
+    pub fn clear_motion_source(&mut self, device_name: &str) {
+        if let Some(device) = self.device_mut(device_name) {
+            device.motion = None;
+        }
+    }
+
+    // Evaluates every device's motion source at `time` (on the caller's clock, e.g. simulated frame time).
+    // While recording, the poses are stamped with that same time so replays line up exactly.
+    pub fn update_all_devices(&mut self, time: Duration) {
+        for device in &mut self.devices {
+            let Some(motion) = &mut device.motion else {
+                continue;
+            };
+
+            let pose = motion.evaluate(time.as_secs_f64());
+            device.pose = pose;
+
+            if let Some(recorder) = &mut self.recorder {
+                if let Err(e) = recorder.record(&device.name, time, pose) {
+                    crit!("Failed to record pose of {}: {}", device.name, e);
+                }
+            }
+        }
+    }
+
+    // `time` is on the same clock as update_all_devices, a recording mixing both stays in order.
     pub fn update_pose(
-        &self,
+        &mut self,
         device_name: &str,
-        session: &OpenXRSession,
         new_pose: xr::Posef,
+        time: Duration,
     ) -> xr::Result<()> {
-        for device in &self.devices {
-            if device.name == device_name {
-                return device.update_pose(session, new_pose);
+        let device = self
+            .device_mut(device_name)
+            .ok_or(xr::sys::Result::ERROR_HANDLE_INVALID)?;  // Return an error if device is not found
+        device.update_pose(new_pose)?;
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record(device_name, time, new_pose) {
+                crit!("Failed to record pose of {}: {}", device_name, e);
             }
         }
-        Err(xr::sys::Result::ERROR_HANDLE_INVALID.into()) // Return an error if device is not found
+        Ok(())
+    }
+
+    // Starts recording every pose update from now on, replacing any recording in progress.
+    pub fn start_recording(&mut self) {
+        info!("Recording device poses");
+        self.recorder = Some(PoseRecorder::new());
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn stop_recording(&mut self) -> Option<PoseRecording> {
+        let recording = self.recorder.take()?.finish();
+        info!("Recorded {} poses for {} devices", recording.samples.len(), recording.devices.len());
+        Some(recording)
+    }
+
+    // Stops recording and writes the binary recording to `path`.
+    pub fn save_recording(&mut self, path: &Path) -> std::io::Result<()> {
+        match self.stop_recording() {
+            Some(recording) => recording.save(path),
+            None => Err(std::io::Error::new(std::io::ErrorKind::Other, "No recording in progress")),
+        }
+    }
+
+    // Devices referenced by the recording but not registered yet are added as detached devices.
+    pub fn start_replay(&mut self, recording: PoseRecording) {
+        for name in &recording.devices {
+            self.ensure_device(name);
+        }
+
+        info!("Replaying {} poses over {:?}", recording.samples.len(), recording.duration());
+        self.replay = Some(PoseReplay::new(recording));
+    }
+
+    pub fn load_replay(&mut self, path: &Path) -> std::io::Result<()> {
+        self.start_replay(PoseRecording::load(path)?);
+        Ok(())
     }
-}
\ No newline at end of file
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // Applies all recorded poses up to `elapsed` since the replay started.
+    // Returns false once the replay has run out of samples (it is then dropped).
+    pub fn advance_replay(&mut self, elapsed: Duration) -> bool {
+        let Some(mut replay) = self.replay.take() else {
+            return false;
+        };
+
+        for (device_name, pose) in replay.advance(elapsed) {
+            if let Some(device) = self.devices.iter_mut().find(|device| device.name == device_name) {
+                device.pose = pose;
+            }
+        }
+
+        if replay.is_finished() {
+            info!("Pose replay finished");
+            false
+        } else {
+            self.replay = Some(replay);
+            true
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::openxr::motion::{LinearSweep, IDENTITY_ROTATION};
+
+    fn hand_pose(x: f32) -> xr::Posef {
+        xr::Posef {
+            orientation: IDENTITY_ROTATION,
+            position: xr::Vector3f { x, y: 1.2, z: -0.3 },
+        }
+    }
+
+    fn frame_time(frame: u64) -> Duration {
+        Duration::from_secs(5) + Duration::from_millis(11) * frame as u32
+    }
+
+    #[test]
+    fn recording_mixes_motion_and_manual_updates_on_one_clock() {
+        let mut devices = DeviceManager::new();
+        devices.add_device(VirtualDevice::detached("head"));
+        devices.add_device(VirtualDevice::detached("right_hand"));
+        devices
+            .set_motion_source(
+                "head",
+                Box::new(LinearSweep {
+                    from: xr::Vector3f { x: 0.0, y: 1.7, z: 0.0 },
+                    to: xr::Vector3f { x: 1.0, y: 1.7, z: 0.0 },
+                    period: 0.1,
+                    orientation: IDENTITY_ROTATION,
+                }),
+            )
+            .unwrap();
+
+        // same order as a desktop frame: local / remote input first, then the motion sources
+        devices.start_recording();
+        let mut expected = Vec::new();
+        for frame in 0..10 {
+            let time = frame_time(frame);
+            devices.update_pose("right_hand", hand_pose(frame as f32 * 0.1), time).unwrap();
+            devices.update_all_devices(time);
+            expected.push((devices.device("head").unwrap().pose, devices.device("right_hand").unwrap().pose));
+        }
+        let recording = devices.stop_recording().unwrap();
+
+        assert_eq!(recording.samples.len(), 20);
+        assert_eq!(recording.samples[0].time, Duration::ZERO);
+        assert!(recording.samples.windows(2).all(|pair| pair[0].time <= pair[1].time));
+
+        let mut bytes = Vec::new();
+        recording.write_binary(&mut bytes).unwrap();
+        let loaded = PoseRecording::read_binary(&mut bytes.as_slice()).unwrap();
+
+        let mut replayed = DeviceManager::new();
+        replayed.start_replay(loaded);
+        for (frame, (head, right_hand)) in expected.into_iter().enumerate() {
+            let running = replayed.advance_replay(frame_time(frame as u64) - frame_time(0));
+            assert_eq!(running, frame < 9);
+            assert_eq!(replayed.device("head").unwrap().pose, head, "head in frame {}", frame);
+            assert_eq!(replayed.device("right_hand").unwrap().pose, right_hand, "right hand in frame {}", frame);
+        }
+    }
+}
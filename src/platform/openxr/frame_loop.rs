@@ -0,0 +1,161 @@
+use openxr as xr;
+
+use crate::platform::openxr::lifecycle::VIEW_TYPE;
+use crate::platform::openxr::session::OpenXRSession;
+use crate::platform::openxr::xr_swapchain::XrSwapchain;
+use crate::platform::vulkan::command::CommandPoolManager;
+use crate::platform::vulkan::context::VulkanContext;
+use crate::platform::vulkan::sync::VulkanSyncObjects;
+
+// Per-frame OpenXR stereo loop:
+// wait_frame -> begin -> locate_views -> acquire / wait image -> record + submit multiview pass -> release -> end with projection layer.
+// Dropping it waits for the frames in flight (sync is dropped first) before the pools and framebuffers go.
+pub struct StereoFrameLoop {
+    sync: VulkanSyncObjects,  // RenderConfig::frames_in_flight slots
+    commands: CommandPoolManager,  // render thread + RenderConfig::recording_threads workers
+    pub swapchain: XrSwapchain,
+    blend_mode: xr::EnvironmentBlendMode,
+}
+
+impl StereoFrameLoop {
+    pub fn new(
+        xr_instance: &xr::Instance,
+        xr_system: xr::SystemId,
+        session: &OpenXRSession,
+        vk_context: &VulkanContext,
+    ) -> xr::Result<Self> {
+        let blend_mode = xr_instance.enumerate_environment_blend_modes(xr_system, VIEW_TYPE)?[0];
+        let swapchain = XrSwapchain::new(xr_instance, xr_system, session, vk_context)?;
+        let image_count = swapchain.images.len();
+
+        let config = &vk_context.config;
+        let sync = VulkanSyncObjects::new(&vk_context.device, config.frames_in_flight, image_count);
+        let commands = CommandPoolManager::new(
+            &vk_context.device,
+            vk_context.queue_family_index,
+            config.frames_in_flight,
+            1 + config.recording_threads,
+        );
+
+        Ok(Self {
+            sync,
+            commands,
+            swapchain,
+            blend_mode,
+        })
+    }
+
+    // Runs one full frame. Must only be called while the session is running (see SessionLifecycle::is_running).
+    pub fn frame(&mut self, vk_context: &VulkanContext, session: &mut OpenXRSession) -> xr::Result<()> {
+        let frame_state = session.frame_wait.wait()?;
+        session.frame_stream.begin()?;
+
+        if !frame_state.should_render {
+            return session.frame_stream.end(frame_state.predicted_display_time, self.blend_mode, &[]);
+        }
+
+        let (_, views) = session.session.locate_views(VIEW_TYPE, frame_state.predicted_display_time, &session.stage)?;
+
+        // throttles the CPU to frames_in_flight frames ahead of the GPU
+        self.sync.begin_frame(&vk_context.device);
+        self.commands.begin_frame(&self.sync);
+
+        let image_index = self.swapchain.handle.acquire_image()? as usize;
+        self.swapchain.handle.wait_image(xr::Duration::INFINITE)?;
+        self.sync.wait_for_image(&vk_context.device, image_index);
+
+        let depth_index = match self.swapchain.depth_swapchain_mut() {
+            Some(depth) => {
+                let depth_index = depth.acquire_image()? as usize;
+                depth.wait_image(xr::Duration::INFINITE)?;
+                depth_index
+            }
+            None => 0,
+        };
+
+        // everything is recorded anew every frame into buffers from the slot's transient pools
+        let command_buffer = self.commands.begin_primary(0);
+        let framebuffer = self.swapchain.framebuffer(image_index, depth_index);
+        let recording_threads = vk_context.config.recording_threads;
+
+        if recording_threads > 0 {
+            vk_context.record_multiview_pass_parallel(
+                &self.commands,
+                command_buffer,
+                framebuffer,
+                self.swapchain.extent,
+                recording_threads,
+            );
+        } else {
+            vk_context.record_multiview_pass(command_buffer, framebuffer, self.swapchain.extent);
+        }
+
+        unsafe {
+            vk_context.device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to record frame command buffer");
+        }
+
+        // The runtime waits on the queue itself, the image only has to be submitted before it is released
+        self.sync.submit(&vk_context.device, vk_context.queue, &[command_buffer]);
+        self.swapchain.handle.release_image()?;
+        if let Some(depth) = self.swapchain.depth_swapchain_mut() {
+            depth.release_image()?;
+        }
+
+        let image_rect = self.swapchain.image_rect();
+
+        // XR_KHR_composition_layer_depth: chained to each projection view, has to outlive frame_stream.end
+        let (near_z, far_z) = vk_context.config.depth_range_meters();
+        let depth_infos = self.swapchain.depth_swapchain().map(|depth| {
+            (0..views.len())
+                .map(|view_index| xr::sys::CompositionLayerDepthInfoKHR {
+                    ty: xr::sys::CompositionLayerDepthInfoKHR::TYPE,
+                    next: std::ptr::null(),
+                    sub_image: xr::sys::SwapchainSubImage {
+                        swapchain: depth.as_raw(),
+                        image_rect,
+                        image_array_index: view_index as u32,
+                    },
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                    near_z,
+                    far_z,
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let projection_views = views
+            .iter()
+            .enumerate()
+            .map(|(view_index, view)| {
+                let projection_view = xr::CompositionLayerProjectionView::new()
+                    .pose(view.pose)
+                    .fov(view.fov)
+                    .sub_image(
+                        xr::SwapchainSubImage::new()
+                            .swapchain(&self.swapchain.handle)
+                            .image_array_index(view_index as u32)
+                            .image_rect(image_rect),
+                    );
+
+                match &depth_infos {
+                    Some(depth_infos) => {
+                        let mut raw = projection_view.into_raw();
+                        raw.next = &depth_infos[view_index] as *const _ as *const _;
+                        unsafe { xr::CompositionLayerProjectionView::from_raw(raw) }
+                    }
+                    None => projection_view,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        session.frame_stream.end(
+            frame_state.predicted_display_time,
+            self.blend_mode,
+            &[&xr::CompositionLayerProjection::new()
+                .space(&session.stage)
+                .views(&projection_views)],
+        )
+    }
+}
@@ -0,0 +1,252 @@
+use openxr as xr;
+
+use crate::platform::openxr::default_bindings::Hand;
+use crate::platform::openxr::hand_tracking::{HandJointId, HandJointPose, HandSkeleton, HAND_JOINT_COUNT};
+use crate::platform::openxr::motion::{
+    quat_from_axis_angle, quat_mul, quat_rotate, vec3_add, vec3_lerp, IDENTITY_ROTATION, X_AXIS, Y_AXIS, Z_AXIS,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandGesture {
+    Open,
+    Fist,
+    Pinch,  // thumb tip touching the index tip
+    Point,  // index extended, other fingers curled
+}
+
+// Per finger layout in the wrist frame of a left hand (-Z towards the fingertips, +Y out of the back of the hand,
+// thumb on +X). The right hand is mirrored across X.
+struct FingerLayout {
+    first_joint: HandJointId,
+    base: xr::Vector3f,
+    spread: f32,  // radians around Y
+    roll: f32,  // radians around Z, turns the thumb's curl towards the palm
+    bone_lengths: &'static [f32],  // metacarpal to tip, one less than the finger's joint count
+    radius: f32,
+}
+
+const FINGERS: [FingerLayout; 5] = [
+    FingerLayout {
+        first_joint: HandJointId::ThumbMetacarpal,
+        base: xr::Vector3f { x: 0.025, y: -0.01, z: -0.02 },
+        spread: -0.6,
+        roll: 0.8,
+        bone_lengths: &[0.040, 0.032, 0.028],
+        radius: 0.010,
+    },
+    FingerLayout {
+        first_joint: HandJointId::IndexMetacarpal,
+        base: xr::Vector3f { x: 0.020, y: 0.0, z: -0.01 },
+        spread: -0.05,
+        roll: 0.0,
+        bone_lengths: &[0.065, 0.040, 0.025, 0.020],
+        radius: 0.009,
+    },
+    FingerLayout {
+        first_joint: HandJointId::MiddleMetacarpal,
+        base: xr::Vector3f { x: 0.0, y: 0.0, z: -0.01 },
+        spread: 0.0,
+        roll: 0.0,
+        bone_lengths: &[0.063, 0.045, 0.028, 0.020],
+        radius: 0.009,
+    },
+    FingerLayout {
+        first_joint: HandJointId::RingMetacarpal,
+        base: xr::Vector3f { x: -0.020, y: 0.0, z: -0.01 },
+        spread: 0.05,
+        roll: 0.0,
+        bone_lengths: &[0.058, 0.042, 0.026, 0.020],
+        radius: 0.008,
+    },
+    FingerLayout {
+        first_joint: HandJointId::LittleMetacarpal,
+        base: xr::Vector3f { x: -0.038, y: 0.0, z: -0.01 },
+        spread: 0.1,
+        roll: 0.0,
+        bone_lengths: &[0.053, 0.033, 0.020, 0.018],
+        radius: 0.007,
+    },
+];
+
+const OPEN_FINGER: [f32; 4] = [0.0, 0.05, 0.05, 0.02];
+const CURLED_FINGER: [f32; 4] = [0.0, 1.5, 1.6, 1.1];
+const RELAXED_FINGER: [f32; 4] = [0.0, 0.3, 0.3, 0.2];
+const OPEN_THUMB: [f32; 4] = [0.0, 0.1, 0.1, 0.0];
+const CURLED_THUMB: [f32; 4] = [0.2, 0.7, 0.9, 0.0];
+
+impl HandGesture {
+    // Bend of each bone towards the palm in radians, per finger (thumb first).
+    fn curls(&self) -> [[f32; 4]; 5] {
+        match self {
+            HandGesture::Open => [OPEN_THUMB, OPEN_FINGER, OPEN_FINGER, OPEN_FINGER, OPEN_FINGER],
+            HandGesture::Fist => [CURLED_THUMB, CURLED_FINGER, CURLED_FINGER, CURLED_FINGER, CURLED_FINGER],
+            HandGesture::Pinch => [[0.2, 0.5, 0.5, 0.0], [0.0, 0.7, 0.8, 0.5], RELAXED_FINGER, RELAXED_FINGER, RELAXED_FINGER],
+            HandGesture::Point => [CURLED_THUMB, OPEN_FINGER, CURLED_FINGER, CURLED_FINGER, CURLED_FINGER],
+        }
+    }
+}
+
+
+// Synthesizes all 26 joints for `hand` with the wrist at `wrist_pose`, in the same space as `wrist_pose`.
+pub fn generate_hand_skeleton(hand: Hand, wrist_pose: xr::Posef, gesture: HandGesture) -> HandSkeleton {
+    let mirror = match hand {
+        Hand::Left => 1.0,
+        Hand::Right => -1.0,
+    };
+    let curls = gesture.curls();
+
+    // joints are built in the wrist frame first and transformed at the end
+    let mut joints = [HandJointPose::INVALID; HAND_JOINT_COUNT];
+    let mut set_joint = |joint: HandJointId, position: xr::Vector3f, orientation: xr::Quaternionf, radius: f32| {
+        joints[joint as usize] = HandJointPose {
+            pose: xr::Posef { orientation, position },
+            radius,
+            position_valid: true,
+            orientation_valid: true,
+        };
+    };
+
+    set_joint(HandJointId::Wrist, xr::Vector3f { x: 0.0, y: 0.0, z: 0.0 }, IDENTITY_ROTATION, 0.02);
+
+    for (finger, layout) in FINGERS.iter().enumerate() {
+        let mut position = xr::Vector3f { x: layout.base.x * mirror, ..layout.base };
+        let mut orientation = quat_mul(
+            quat_from_axis_angle(Y_AXIS, layout.spread * mirror),
+            quat_from_axis_angle(Z_AXIS, layout.roll * mirror),
+        );
+
+        for (bone, length) in layout.bone_lengths.iter().enumerate() {
+            orientation = quat_mul(orientation, quat_from_axis_angle(X_AXIS, -curls[finger][bone]));
+            set_joint(joint_at(layout.first_joint, bone), position, orientation, layout.radius);
+            position = vec3_add(position, quat_rotate(orientation, xr::Vector3f { x: 0.0, y: 0.0, z: -length }));
+        }
+
+        let tip = joint_at(layout.first_joint, layout.bone_lengths.len());
+        set_joint(tip, position, orientation, layout.radius * 0.7);
+    }
+
+    // palm sits halfway along the middle metacarpal
+    let middle_base = joints[HandJointId::MiddleMetacarpal as usize].pose.position;
+    let middle_proximal = joints[HandJointId::MiddleProximal as usize].pose.position;
+    joints[HandJointId::Palm as usize] = HandJointPose {
+        pose: xr::Posef { orientation: IDENTITY_ROTATION, position: vec3_lerp(middle_base, middle_proximal, 0.5) },
+        radius: 0.03,
+        position_valid: true,
+        orientation_valid: true,
+    };
+
+    // the curl model can't close the loop between thumb and index, snap the thumb onto the index tip instead
+    if gesture == HandGesture::Pinch {
+        let index_tip = joints[HandJointId::IndexTip as usize].pose.position;
+        let thumb_proximal = joints[HandJointId::ThumbProximal as usize].pose.position;
+        joints[HandJointId::ThumbTip as usize].pose.position = index_tip;
+        joints[HandJointId::ThumbDistal as usize].pose.position = vec3_lerp(thumb_proximal, index_tip, 0.55);
+    }
+
+    for joint in joints.iter_mut() {
+        joint.pose = xr::Posef {
+            orientation: quat_mul(wrist_pose.orientation, joint.pose.orientation),
+            position: vec3_add(wrist_pose.position, quat_rotate(wrist_pose.orientation, joint.pose.position)),
+        };
+    }
+
+    HandSkeleton { joints }
+}
+
+fn joint_at(first_joint: HandJointId, offset: usize) -> HandJointId {
+    HandJointId::ALL[first_joint as usize + offset]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::openxr::motion::{pose_mul, vec3_length, vec3_sub};
+
+    const GESTURES: [HandGesture; 4] = [HandGesture::Open, HandGesture::Fist, HandGesture::Pinch, HandGesture::Point];
+    const TIPS: [HandJointId; 5] = [
+        HandJointId::ThumbTip,
+        HandJointId::IndexTip,
+        HandJointId::MiddleTip,
+        HandJointId::RingTip,
+        HandJointId::LittleTip,
+    ];
+
+    fn distance(a: xr::Vector3f, b: xr::Vector3f) -> f32 {
+        vec3_length(vec3_sub(a, b))
+    }
+
+    #[test]
+    fn every_joint_is_valid() {
+        for hand in Hand::ALL {
+            for gesture in GESTURES {
+                let skeleton = generate_hand_skeleton(hand, xr::Posef::IDENTITY, gesture);
+                for (joint, pose) in HandJointId::ALL.iter().zip(skeleton.joints.iter()) {
+                    assert!(pose.position_valid && pose.orientation_valid, "{:?} {:?} {:?}", hand, gesture, joint);
+                    assert!(pose.radius > 0.0, "{:?} {:?} {:?}", hand, gesture, joint);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pinch_touches_thumb_and_index_tips() {
+        for hand in Hand::ALL {
+            let skeleton = generate_hand_skeleton(hand, xr::Posef::IDENTITY, HandGesture::Pinch);
+            assert!(skeleton.pinch_distance() < 1e-6);
+
+            let open = generate_hand_skeleton(hand, xr::Posef::IDENTITY, HandGesture::Open);
+            assert!(open.pinch_distance() > 0.02);
+        }
+    }
+
+    #[test]
+    fn fist_curls_the_tips_towards_the_palm() {
+        let open = generate_hand_skeleton(Hand::Left, xr::Posef::IDENTITY, HandGesture::Open);
+        let fist = generate_hand_skeleton(Hand::Left, xr::Posef::IDENTITY, HandGesture::Fist);
+        let to_palm = |skeleton: &HandSkeleton, tip: HandJointId| {
+            distance(skeleton.joint(tip).pose.position, skeleton.joint(HandJointId::Palm).pose.position)
+        };
+
+        for tip in TIPS {
+            assert!(to_palm(&fist, tip) < to_palm(&open, tip), "{:?}", tip);
+        }
+    }
+
+    #[test]
+    fn right_hand_mirrors_the_left_across_x() {
+        for gesture in GESTURES {
+            let left = generate_hand_skeleton(Hand::Left, xr::Posef::IDENTITY, gesture);
+            let right = generate_hand_skeleton(Hand::Right, xr::Posef::IDENTITY, gesture);
+
+            for (joint, (left, right)) in HandJointId::ALL.iter().zip(left.joints.iter().zip(right.joints.iter())) {
+                let mirrored = xr::Vector3f { x: -left.pose.position.x, ..left.pose.position };
+                assert!(distance(right.pose.position, mirrored) < 1e-6, "{:?} {:?}", gesture, joint);
+                assert_eq!(right.radius, left.radius);
+            }
+        }
+    }
+
+    #[test]
+    fn joints_follow_the_wrist_pose() {
+        let wrist_pose = xr::Posef {
+            orientation: quat_from_axis_angle(xr::Vector3f { x: 0.3, y: 1.0, z: -0.2 }, 1.1),
+            position: xr::Vector3f { x: 0.4, y: 1.2, z: -0.5 },
+        };
+
+        for gesture in GESTURES {
+            let local = generate_hand_skeleton(Hand::Right, xr::Posef::IDENTITY, gesture);
+            let placed = generate_hand_skeleton(Hand::Right, wrist_pose, gesture);
+
+            assert!(distance(placed.joint(HandJointId::Wrist).pose.position, wrist_pose.position) < 1e-6);
+            for (joint, (local, placed)) in HandJointId::ALL.iter().zip(local.joints.iter().zip(placed.joints.iter())) {
+                let expected = pose_mul(wrist_pose, local.pose);
+                assert!(distance(placed.pose.position, expected.position) < 1e-5, "{:?} {:?}", gesture, joint);
+
+                let (a, b) = (placed.pose.orientation, expected.orientation);
+                let dot = a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+                assert!(dot.abs() > 1.0 - 1e-5, "{:?} {:?}", gesture, joint);
+            }
+        }
+    }
+}
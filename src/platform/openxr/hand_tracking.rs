@@ -0,0 +1,181 @@
+use openxr as xr;
+use mlog::*;
+
+use crate::platform::openxr::default_bindings::Hand;
+
+pub const HAND_JOINT_COUNT: usize = 26;
+
+// Joint order of XR_EXT_hand_tracking, usable as index into HandSkeleton::joints.
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandJointId {
+    Palm,
+    Wrist,
+    ThumbMetacarpal,
+    ThumbProximal,
+    ThumbDistal,
+    ThumbTip,
+    IndexMetacarpal,
+    IndexProximal,
+    IndexIntermediate,
+    IndexDistal,
+    IndexTip,
+    MiddleMetacarpal,
+    MiddleProximal,
+    MiddleIntermediate,
+    MiddleDistal,
+    MiddleTip,
+    RingMetacarpal,
+    RingProximal,
+    RingIntermediate,
+    RingDistal,
+    RingTip,
+    LittleMetacarpal,
+    LittleProximal,
+    LittleIntermediate,
+    LittleDistal,
+    LittleTip,
+}
+
+impl HandJointId {
+    pub const ALL: [HandJointId; HAND_JOINT_COUNT] = [
+        HandJointId::Palm,
+        HandJointId::Wrist,
+        HandJointId::ThumbMetacarpal,
+        HandJointId::ThumbProximal,
+        HandJointId::ThumbDistal,
+        HandJointId::ThumbTip,
+        HandJointId::IndexMetacarpal,
+        HandJointId::IndexProximal,
+        HandJointId::IndexIntermediate,
+        HandJointId::IndexDistal,
+        HandJointId::IndexTip,
+        HandJointId::MiddleMetacarpal,
+        HandJointId::MiddleProximal,
+        HandJointId::MiddleIntermediate,
+        HandJointId::MiddleDistal,
+        HandJointId::MiddleTip,
+        HandJointId::RingMetacarpal,
+        HandJointId::RingProximal,
+        HandJointId::RingIntermediate,
+        HandJointId::RingDistal,
+        HandJointId::RingTip,
+        HandJointId::LittleMetacarpal,
+        HandJointId::LittleProximal,
+        HandJointId::LittleIntermediate,
+        HandJointId::LittleDistal,
+        HandJointId::LittleTip,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HandJointPose {
+    pub pose: xr::Posef,
+    pub radius: f32,  // meters
+    pub position_valid: bool,
+    pub orientation_valid: bool,
+}
+
+impl HandJointPose {
+    pub const INVALID: HandJointPose = HandJointPose {
+        pose: xr::Posef::IDENTITY,
+        radius: 0.0,
+        position_valid: false,
+        orientation_valid: false,
+    };
+}
+
+// All 26 joints of one hand, either located by the runtime or generated by the emulation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HandSkeleton {
+    pub joints: [HandJointPose; HAND_JOINT_COUNT],
+}
+
+impl HandSkeleton {
+    pub fn joint(&self, joint: HandJointId) -> &HandJointPose {
+        &self.joints[joint as usize]
+    }
+
+    pub fn joint_mut(&mut self, joint: HandJointId) -> &mut HandJointPose {
+        &mut self.joints[joint as usize]
+    }
+
+    // Distance between thumb and index tip, the usual pinch detector.
+    pub fn pinch_distance(&self) -> f32 {
+        let thumb = self.joint(HandJointId::ThumbTip).pose.position;
+        let index = self.joint(HandJointId::IndexTip).pose.position;
+        let (dx, dy, dz) = (thumb.x - index.x, thumb.y - index.y, thumb.z - index.z);
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+
+    fn from_locations(locations: &xr::HandJointLocations) -> Self {
+        let mut joints = [HandJointPose::INVALID; HAND_JOINT_COUNT];
+        for (joint, location) in joints.iter_mut().zip(locations.iter()) {
+            *joint = HandJointPose {
+                pose: location.pose,
+                radius: location.radius,
+                position_valid: location.location_flags.contains(xr::SpaceLocationFlags::POSITION_VALID),
+                orientation_valid: location.location_flags.contains(xr::SpaceLocationFlags::ORIENTATION_VALID),
+            };
+        }
+        Self { joints }
+    }
+}
+
+
+// Hand trackers for both hands, only available when XR_EXT_hand_tracking was enabled on the instance
+// and the system supports it.
+pub struct HandTracking {
+    left: xr::HandTracker,
+    right: xr::HandTracker,
+}
+
+impl HandTracking {
+    pub fn is_supported(xr_instance: &xr::Instance, system: xr::SystemId) -> xr::Result<bool> {
+        if xr_instance.exts().ext_hand_tracking.is_none() {
+            return Ok(false);
+        }
+        xr_instance.supports_hand_tracking(system)
+    }
+
+    // Returns None instead of an error when hand tracking isn't available.
+    pub fn new(
+        xr_instance: &xr::Instance,
+        system: xr::SystemId,
+        session: &xr::Session<xr::Vulkan>,
+    ) -> xr::Result<Option<Self>> {
+        if !Self::is_supported(xr_instance, system)? {
+            info!("Hand tracking not available");
+            return Ok(None);
+        }
+
+        let tracking = Self {
+            left: session.create_hand_tracker(xr::Hand::LEFT)?,
+            right: session.create_hand_tracker(xr::Hand::RIGHT)?,
+        };
+        success!("Created hand trackers");
+        Ok(Some(tracking))
+    }
+
+    pub fn tracker(&self, hand: Hand) -> &xr::HandTracker {
+        match hand {
+            Hand::Left => &self.left,
+            Hand::Right => &self.right,
+        }
+    }
+
+    // Joints relative to `base_space` at `time`, None while the hand isn't tracked.
+    pub fn locate(&self, hand: Hand, base_space: &xr::Space, time: xr::Time) -> xr::Result<Option<HandSkeleton>> {
+        Ok(base_space
+            .locate_hand_joints(self.tracker(hand), time)?
+            .map(|locations| HandSkeleton::from_locations(&locations)))
+    }
+
+    // [left, right]
+    pub fn locate_both(&self, base_space: &xr::Space, time: xr::Time) -> xr::Result<[Option<HandSkeleton>; 2]> {
+        Ok([
+            self.locate(Hand::Left, base_space, time)?,
+            self.locate(Hand::Right, base_space, time)?,
+        ])
+    }
+}
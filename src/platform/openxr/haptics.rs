@@ -0,0 +1,373 @@
+use openxr as xr;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::platform::openxr::action_set::ActionSet;
+use crate::platform::openxr::default_bindings::{Hand, HAPTIC_ACTION};
+use crate::platform::openxr::device_emulation::DeviceManager;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HapticPulse {
+    pub amplitude: f32,  // 0.0 - 1.0
+    pub frequency: Option<f32>,  // Hz, None lets the runtime pick
+    pub duration: Duration,
+}
+
+impl HapticPulse {
+    pub fn new(amplitude: f32, duration: Duration) -> Self {
+        Self {
+            amplitude: amplitude.clamp(0.0, 1.0),
+            frequency: None,
+            duration,
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = Some(frequency);
+        self
+    }
+
+    // Shortest pulse the runtime supports, a single "tick".
+    pub fn tick(amplitude: f32) -> Self {
+        Self::new(amplitude, Duration::ZERO)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HapticStep {
+    Pulse(HapticPulse),
+    Pause(Duration),
+}
+
+impl HapticStep {
+    pub fn duration(&self) -> Duration {
+        match self {
+            HapticStep::Pulse(pulse) => pulse.duration,
+            HapticStep::Pause(duration) => *duration,
+        }
+    }
+}
+
+// Sequence of pulses and pauses, played back by a HapticPlayer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HapticPattern {
+    pub steps: Vec<HapticStep>,
+}
+
+impl HapticPattern {
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    pub fn pulse(mut self, pulse: HapticPulse) -> Self {
+        self.steps.push(HapticStep::Pulse(pulse));
+        self
+    }
+
+    pub fn pause(mut self, duration: Duration) -> Self {
+        self.steps.push(HapticStep::Pause(duration));
+        self
+    }
+
+    // `count` pulses separated by `gap`.
+    pub fn repeat(pulse: HapticPulse, count: usize, gap: Duration) -> Self {
+        let mut pattern = Self::new();
+        for i in 0..count {
+            if i > 0 {
+                pattern = pattern.pause(gap);
+            }
+            pattern = pattern.pulse(pulse);
+        }
+        pattern
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(HapticStep::duration).sum()
+    }
+}
+
+
+// Anything that can vibrate a hand: the OpenXR runtime or the device emulation.
+pub trait HapticOutput {
+    fn apply_haptic(&mut self, hand: Hand, pulse: &HapticPulse) -> xr::Result<()>;
+    fn stop_haptic(&mut self, hand: Hand) -> xr::Result<()>;
+}
+
+// Drives the manifest's "haptic" action with the hand as subaction path.
+pub struct XrHapticOutput<'a> {
+    action_set: &'a ActionSet,
+    session: &'a xr::Session<xr::Vulkan>,
+}
+
+impl<'a> XrHapticOutput<'a> {
+    pub fn new(action_set: &'a ActionSet, session: &'a xr::Session<xr::Vulkan>) -> Self {
+        Self { action_set, session }
+    }
+
+    fn action(&self) -> xr::Result<&'a xr::Action<xr::Haptic>> {
+        self.action_set
+            .haptic(HAPTIC_ACTION)
+            .ok_or_else(|| self.action_set.lookup_error(HAPTIC_ACTION))
+    }
+}
+
+impl HapticOutput for XrHapticOutput<'_> {
+    fn apply_haptic(&mut self, hand: Hand, pulse: &HapticPulse) -> xr::Result<()> {
+        let duration = if pulse.duration.is_zero() {
+            xr::Duration::MIN_HAPTIC
+        } else {
+            xr::Duration::from_nanos(pulse.duration.as_nanos() as i64)
+        };
+
+        let vibration = xr::HapticVibration::new()
+            .amplitude(pulse.amplitude)
+            .frequency(pulse.frequency.unwrap_or(xr::FREQUENCY_UNSPECIFIED))
+            .duration(duration);
+
+        self.action()?.apply_feedback(self.session, self.action_set.hand_path(hand), &vibration)
+    }
+
+    fn stop_haptic(&mut self, hand: Hand) -> xr::Result<()> {
+        self.action()?.stop_feedback(self.session, self.action_set.hand_path(hand))
+    }
+}
+
+impl ActionSet {
+    pub fn haptics<'a>(&'a self, session: &'a xr::Session<xr::Vulkan>) -> XrHapticOutput<'a> {
+        XrHapticOutput::new(self, session)
+    }
+
+    pub fn apply_haptic(&self, session: &xr::Session<xr::Vulkan>, hand: Hand, pulse: &HapticPulse) -> xr::Result<()> {
+        self.haptics(session).apply_haptic(hand, pulse)
+    }
+
+    pub fn stop_haptic(&self, session: &xr::Session<xr::Vulkan>, hand: Hand) -> xr::Result<()> {
+        self.haptics(session).stop_haptic(hand)
+    }
+}
+
+// Emulated hands record every event on their VirtualDevice, see VirtualDevice::haptic_events.
+impl HapticOutput for DeviceManager {
+    fn apply_haptic(&mut self, hand: Hand, pulse: &HapticPulse) -> xr::Result<()> {
+        self.ensure_device(hand.device_name()).push_haptic_event(HapticEvent::Pulse(*pulse));
+        Ok(())
+    }
+
+    fn stop_haptic(&mut self, hand: Hand) -> xr::Result<()> {
+        self.ensure_device(hand.device_name()).push_haptic_event(HapticEvent::Stop);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HapticEvent {
+    Pulse(HapticPulse),
+    Stop,
+}
+
+
+struct PlayingPattern {
+    pattern: HapticPattern,
+    start: Duration,
+    next_step: usize,
+}
+
+// Plays patterns on a HapticOutput, `update` has to be called every frame with the current time.
+pub struct HapticPlayer {
+    playing: HashMap<Hand, PlayingPattern>,
+    pending_stops: Vec<Hand>,
+}
+
+impl HapticPlayer {
+    pub fn new() -> Self {
+        Self {
+            playing: HashMap::new(),
+            pending_stops: Vec::new(),
+        }
+    }
+
+    // Replaces whatever is playing on `hand`, the first step is issued on the next `update`.
+    pub fn play(&mut self, hand: Hand, pattern: HapticPattern, now: Duration) {
+        self.playing.insert(hand, PlayingPattern { pattern, start: now, next_step: 0 });
+    }
+
+    pub fn stop(&mut self, hand: Hand) {
+        self.playing.remove(&hand);
+        if !self.pending_stops.contains(&hand) {
+            self.pending_stops.push(hand);
+        }
+    }
+
+    pub fn is_playing(&self, hand: Hand) -> bool {
+        self.playing.contains_key(&hand)
+    }
+
+    // A failing output doesn't stall playback: every stop and due step is still attempted, stops that failed are
+    // retried on the next update and the first error is returned.
+    pub fn update(&mut self, now: Duration, output: &mut impl HapticOutput) -> xr::Result<()> {
+        let mut first_error = None;
+
+        self.pending_stops.retain(|&hand| match output.stop_haptic(hand) {
+            Ok(()) => false,
+            Err(e) => {
+                first_error.get_or_insert(e);
+                true
+            }
+        });
+
+        let mut finished = Vec::new();
+        for (hand, playing) in &mut self.playing {
+            let mut step_start = playing.start + playing.pattern.steps[..playing.next_step]
+                .iter()
+                .map(HapticStep::duration)
+                .sum::<Duration>();
+
+            while let Some(step) = playing.pattern.steps.get(playing.next_step) {
+                if step_start > now {
+                    break;
+                }
+                if let HapticStep::Pulse(pulse) = step {
+                    if let Err(e) = output.apply_haptic(*hand, pulse) {
+                        first_error.get_or_insert(e);
+                    }
+                }
+                step_start += step.duration();
+                playing.next_step += 1;
+            }
+
+            if playing.next_step == playing.pattern.steps.len() {
+                finished.push(*hand);
+            }
+        }
+
+        for hand in finished {
+            self.playing.remove(&hand);
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    // Fails every call for the hands in `failing`, records the rest.
+    struct FlakyOutput {
+        failing: Vec<Hand>,
+        events: Vec<(Hand, HapticEvent)>,
+    }
+
+    impl HapticOutput for FlakyOutput {
+        fn apply_haptic(&mut self, hand: Hand, pulse: &HapticPulse) -> xr::Result<()> {
+            if self.failing.contains(&hand) {
+                return Err(xr::sys::Result::ERROR_SESSION_LOST);
+            }
+            self.events.push((hand, HapticEvent::Pulse(*pulse)));
+            Ok(())
+        }
+
+        fn stop_haptic(&mut self, hand: Hand) -> xr::Result<()> {
+            if self.failing.contains(&hand) {
+                return Err(xr::sys::Result::ERROR_SESSION_LOST);
+            }
+            self.events.push((hand, HapticEvent::Stop));
+            Ok(())
+        }
+    }
+
+    fn haptic_events(devices: &DeviceManager, hand: Hand) -> Vec<HapticEvent> {
+        devices.device(hand.device_name()).map_or_else(Vec::new, |device| device.haptic_events().to_vec())
+    }
+
+    #[test]
+    fn steps_are_issued_when_due() {
+        let mut devices = DeviceManager::new();
+        let mut player = HapticPlayer::new();
+        let first = HapticPulse::new(1.0, 10 * MS);
+        let second = HapticPulse::new(0.5, 10 * MS);
+
+        player.play(Hand::Right, HapticPattern::new().pulse(first).pause(20 * MS).pulse(second), Duration::ZERO);
+
+        player.update(Duration::ZERO, &mut devices).unwrap();
+        assert_eq!(haptic_events(&devices, Hand::Right), [HapticEvent::Pulse(first)]);
+
+        player.update(29 * MS, &mut devices).unwrap();
+        assert_eq!(haptic_events(&devices, Hand::Right), [HapticEvent::Pulse(first)]);
+        assert!(player.is_playing(Hand::Right));
+
+        player.update(30 * MS, &mut devices).unwrap();
+        assert_eq!(haptic_events(&devices, Hand::Right), [HapticEvent::Pulse(first), HapticEvent::Pulse(second)]);
+        assert!(!player.is_playing(Hand::Right));
+        assert!(haptic_events(&devices, Hand::Left).is_empty());
+    }
+
+    #[test]
+    fn late_update_catches_up_on_missed_steps() {
+        let mut devices = DeviceManager::new();
+        let mut player = HapticPlayer::new();
+        let pulse = HapticPulse::tick(1.0);
+
+        player.play(Hand::Left, HapticPattern::repeat(pulse, 3, 5 * MS), Duration::ZERO);
+        player.update(100 * MS, &mut devices).unwrap();
+
+        assert_eq!(haptic_events(&devices, Hand::Left), [HapticEvent::Pulse(pulse); 3]);
+        assert!(!player.is_playing(Hand::Left));
+    }
+
+    #[test]
+    fn stop_cancels_the_pattern_and_stops_the_device() {
+        let mut devices = DeviceManager::new();
+        let mut player = HapticPlayer::new();
+        let pulse = HapticPulse::new(1.0, 10 * MS);
+
+        player.play(Hand::Left, HapticPattern::repeat(pulse, 2, 10 * MS), Duration::ZERO);
+        player.update(Duration::ZERO, &mut devices).unwrap();
+        player.stop(Hand::Left);
+        player.update(50 * MS, &mut devices).unwrap();
+
+        assert_eq!(haptic_events(&devices, Hand::Left), [HapticEvent::Pulse(pulse), HapticEvent::Stop]);
+        assert!(!player.is_playing(Hand::Left));
+    }
+
+    #[test]
+    fn failing_output_does_not_drop_other_hands() {
+        let mut output = FlakyOutput { failing: vec![Hand::Left], events: Vec::new() };
+        let mut player = HapticPlayer::new();
+        let pulse = HapticPulse::tick(1.0);
+
+        player.play(Hand::Right, HapticPattern::new().pulse(pulse), Duration::ZERO);
+        player.stop(Hand::Left);
+        player.play(Hand::Left, HapticPattern::new().pulse(pulse), Duration::ZERO);
+
+        assert_eq!(player.update(Duration::ZERO, &mut output), Err(xr::sys::Result::ERROR_SESSION_LOST));
+        assert_eq!(output.events, [(Hand::Right, HapticEvent::Pulse(pulse))]);
+        assert!(!player.is_playing(Hand::Right));
+    }
+
+    #[test]
+    fn failed_stops_are_retried() {
+        let mut output = FlakyOutput { failing: vec![Hand::Left], events: Vec::new() };
+        let mut player = HapticPlayer::new();
+
+        player.stop(Hand::Left);
+        player.stop(Hand::Right);
+        assert!(player.update(Duration::ZERO, &mut output).is_err());
+        assert_eq!(output.events, [(Hand::Right, HapticEvent::Stop)]);
+
+        output.failing.clear();
+        player.update(MS, &mut output).unwrap();
+        assert_eq!(output.events, [(Hand::Right, HapticEvent::Stop), (Hand::Left, HapticEvent::Stop)]);
+
+        player.update(2 * MS, &mut output).unwrap();
+        assert_eq!(output.events.len(), 2);
+    }
+}
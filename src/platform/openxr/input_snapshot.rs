@@ -0,0 +1,277 @@
+use openxr as xr;
+
+use std::collections::HashMap;
+
+use crate::platform::openxr::action_set::{ActionSet, AnyAction};
+use crate::platform::openxr::default_bindings::{Hand, AIM_POSE_ACTION, GRIP_POSE_ACTION};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActionValue<T> {
+    pub current: T,
+    pub changed_since_last_sync: bool,
+    pub is_active: bool,  // false when nothing is bound or the action set isn't focused
+    pub last_change_time: xr::Time,
+}
+
+impl<T> From<xr::ActionState<T>> for ActionValue<T> {
+    fn from(state: xr::ActionState<T>) -> Self {
+        Self {
+            current: state.current_state,
+            changed_since_last_sync: state.changed_since_last_sync,
+            is_active: state.is_active,
+            last_change_time: state.last_change_time,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocatedPose {
+    pub pose: xr::Posef,  // relative to the reference space passed to `sync_and_snapshot`
+    pub position_valid: bool,
+    pub orientation_valid: bool,
+    pub position_tracked: bool,  // false when the runtime is only inferring the position
+    pub orientation_tracked: bool,
+}
+
+impl LocatedPose {
+    // None when neither position nor orientation could be located.
+    pub fn from_location(location: &xr::SpaceLocation) -> Option<Self> {
+        let flags = location.location_flags;
+        let position_valid = flags.contains(xr::SpaceLocationFlags::POSITION_VALID);
+        let orientation_valid = flags.contains(xr::SpaceLocationFlags::ORIENTATION_VALID);
+
+        if !position_valid && !orientation_valid {
+            return None;
+        }
+
+        Some(Self {
+            pose: location.pose,
+            position_valid,
+            orientation_valid,
+            position_tracked: flags.contains(xr::SpaceLocationFlags::POSITION_TRACKED),
+            orientation_tracked: flags.contains(xr::SpaceLocationFlags::ORIENTATION_TRACKED),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoseState {
+    pub is_active: bool,
+    pub location: Option<LocatedPose>,
+}
+
+// Values of every non-haptic action for one subaction path, keyed by action name.
+#[derive(Clone, Debug, Default)]
+pub struct ActionValues {
+    pub booleans: HashMap<String, ActionValue<bool>>,
+    pub floats: HashMap<String, ActionValue<f32>>,
+    pub vector2fs: HashMap<String, ActionValue<xr::Vector2f>>,
+    pub poses: HashMap<String, PoseState>,
+}
+
+impl ActionValues {
+    pub fn boolean(&self, name: &str) -> bool {
+        self.booleans.get(name).map_or(false, |value| value.current)
+    }
+
+    // True only in the snapshot where the action went from released to pressed.
+    pub fn pressed(&self, name: &str) -> bool {
+        self.booleans
+            .get(name)
+            .map_or(false, |value| value.current && value.changed_since_last_sync)
+    }
+
+    pub fn released(&self, name: &str) -> bool {
+        self.booleans
+            .get(name)
+            .map_or(false, |value| !value.current && value.changed_since_last_sync)
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        self.floats.get(name).map_or(0.0, |value| value.current)
+    }
+
+    pub fn vector2f(&self, name: &str) -> xr::Vector2f {
+        self.vector2fs
+            .get(name)
+            .map_or(xr::Vector2f { x: 0.0, y: 0.0 }, |value| value.current)
+    }
+
+    pub fn pose(&self, name: &str) -> Option<LocatedPose> {
+        self.poses.get(name).and_then(|state| state.location)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HandInput {
+    pub values: ActionValues,
+    pub grip: Option<LocatedPose>,
+    pub aim: Option<LocatedPose>,
+}
+
+// Everything the action system reported at one sync. Actions declared with hand subaction paths are
+// split per hand, actions without subaction paths end up in `global`. Other subaction paths are ignored.
+#[derive(Clone, Debug)]
+pub struct InputSnapshot {
+    pub time: xr::Time,
+    pub global: ActionValues,
+    pub left: HandInput,
+    pub right: HandInput,
+}
+
+impl InputSnapshot {
+    pub fn hand(&self, hand: Hand) -> &HandInput {
+        match hand {
+            Hand::Left => &self.left,
+            Hand::Right => &self.right,
+        }
+    }
+}
+
+
+impl ActionSet {
+    pub fn sync(&self, session: &xr::Session<xr::Vulkan>) -> xr::Result<()> {
+        let active_sets = self.action_sets.iter().map(xr::ActiveActionSet::new).collect::<Vec<_>>();
+        session.sync_actions(&active_sets)
+    }
+
+    // Syncs all action sets and reads back every action, locating pose actions in `reference_space` at `time`
+    // (usually the frame's predicted display time). Requires `attach` to have been called.
+    pub fn sync_and_snapshot(
+        &self,
+        session: &xr::Session<xr::Vulkan>,
+        reference_space: &xr::Space,
+        time: xr::Time,
+    ) -> xr::Result<InputSnapshot> {
+        self.sync(session)?;
+
+        let mut global = ActionValues::default();
+        let mut left = HandInput::default();
+        let mut right = HandInput::default();
+
+        for (name, entry) in self.entries() {
+            let subaction_paths = if entry.subaction_paths.is_empty() {
+                vec![xr::Path::NULL]
+            } else {
+                entry.subaction_paths.clone()
+            };
+
+            for path in subaction_paths {
+                let values = if path == xr::Path::NULL {
+                    &mut global
+                } else if path == self.hand_path(Hand::Left) {
+                    &mut left.values
+                } else if path == self.hand_path(Hand::Right) {
+                    &mut right.values
+                } else {
+                    continue;
+                };
+
+                match &entry.action {
+                    AnyAction::Boolean(action) => {
+                        values.booleans.insert(name.to_string(), action.state(session, path)?.into());
+                    }
+                    AnyAction::Float(action) => {
+                        values.floats.insert(name.to_string(), action.state(session, path)?.into());
+                    }
+                    AnyAction::Vector2f(action) => {
+                        values.vector2fs.insert(name.to_string(), action.state(session, path)?.into());
+                    }
+                    AnyAction::Pose(action) => {
+                        let location = match self.pose_space(name, path) {
+                            Some(space) => LocatedPose::from_location(&space.locate(reference_space, time)?),
+                            None => None,
+                        };
+                        values.poses.insert(
+                            name.to_string(),
+                            PoseState {
+                                is_active: action.is_active(session, path)?,
+                                location,
+                            },
+                        );
+                    }
+                    AnyAction::Haptic(_) => {}
+                }
+            }
+        }
+
+        for hand in [&mut left, &mut right] {
+            hand.grip = hand.values.pose(GRIP_POSE_ACTION);
+            hand.aim = hand.values.pose(AIM_POSE_ACTION);
+        }
+
+        Ok(InputSnapshot { time, global, left, right })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn location(location_flags: xr::SpaceLocationFlags) -> xr::SpaceLocation {
+        xr::SpaceLocation {
+            location_flags,
+            pose: xr::Posef {
+                orientation: xr::Quaternionf { x: 0.0, y: FRAC_1_SQRT_2, z: 0.0, w: FRAC_1_SQRT_2 },
+                position: xr::Vector3f { x: 0.1, y: 1.2, z: -0.3 },
+            },
+        }
+    }
+
+    fn boolean(current: bool, changed_since_last_sync: bool) -> ActionValue<bool> {
+        ActionValue {
+            current,
+            changed_since_last_sync,
+            is_active: true,
+            last_change_time: xr::Time::from_nanos(0),
+        }
+    }
+
+    #[test]
+    fn unlocated_space_has_no_pose() {
+        assert_eq!(LocatedPose::from_location(&location(xr::SpaceLocationFlags::empty())), None);
+    }
+
+    #[test]
+    fn located_pose_keeps_valid_and_tracked_flags_apart() {
+        let inferred = LocatedPose::from_location(&location(xr::SpaceLocationFlags::ORIENTATION_VALID)).unwrap();
+        assert!(inferred.orientation_valid && !inferred.position_valid);
+        assert!(!inferred.orientation_tracked && !inferred.position_tracked);
+
+        let flags = xr::SpaceLocationFlags::POSITION_VALID
+            | xr::SpaceLocationFlags::ORIENTATION_VALID
+            | xr::SpaceLocationFlags::POSITION_TRACKED;
+        let located = LocatedPose::from_location(&location(flags)).unwrap();
+        assert_eq!(located.pose, location(flags).pose);
+        assert!(located.position_valid && located.orientation_valid);
+        assert!(located.position_tracked && !located.orientation_tracked);
+    }
+
+    #[test]
+    fn pressed_and_released_only_on_the_changing_sync() {
+        let mut values = ActionValues::default();
+        values.booleans.insert("pressed".to_string(), boolean(true, true));
+        values.booleans.insert("held".to_string(), boolean(true, false));
+        values.booleans.insert("released".to_string(), boolean(false, true));
+        values.booleans.insert("idle".to_string(), boolean(false, false));
+
+        assert!(values.pressed("pressed") && !values.released("pressed") && values.boolean("pressed"));
+        assert!(!values.pressed("held") && !values.released("held") && values.boolean("held"));
+        assert!(!values.pressed("released") && values.released("released") && !values.boolean("released"));
+        assert!(!values.pressed("idle") && !values.released("idle") && !values.boolean("idle"));
+    }
+
+    #[test]
+    fn missing_actions_read_as_defaults() {
+        let values = ActionValues::default();
+        assert!(!values.boolean("select"));
+        assert!(!values.pressed("select"));
+        assert!(!values.released("select"));
+        assert_eq!(values.float("trigger"), 0.0);
+        assert_eq!(values.vector2f("thumbstick"), xr::Vector2f { x: 0.0, y: 0.0 });
+        assert_eq!(values.pose("grip_pose"), None);
+    }
+}
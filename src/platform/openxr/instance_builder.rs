@@ -0,0 +1,193 @@
+use openxr as xr;
+use mlog::*;
+
+// Accessor for the matching flag in xr::ExtensionSet, e.g. `|extensions| &mut extensions.ext_hand_tracking`.
+pub type ExtensionField = fn(&mut xr::ExtensionSet) -> &mut bool;
+
+struct ExtensionRequest {
+    name: String,
+    field: Option<ExtensionField>,  // None for extensions openxr-rs doesn't know, those go through `other`
+    required: bool,
+}
+
+struct LayerRequest {
+    name: String,
+    required: bool,
+}
+
+// Collects the extensions and API layers requested by the engine's features, checks them against what the
+// runtime offers and creates the instance. Missing required entries fail, missing optional ones are skipped.
+pub struct OpenXRInstanceBuilder<'a> {
+    entry: &'a xr::Entry,
+    application_name: String,
+    application_version: u32,
+    extensions: Vec<ExtensionRequest>,
+    layers: Vec<LayerRequest>,
+}
+
+impl<'a> OpenXRInstanceBuilder<'a> {
+    pub fn new(entry: &'a xr::Entry) -> Self {
+        Self {
+            entry,
+            application_name: "Neon".to_string(),
+            application_version: 0,
+            extensions: Vec::new(),
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn application(mut self, name: &str, version: u32) -> Self {
+        self.application_name = name.to_string();
+        self.application_version = version;
+        self
+    }
+
+    pub fn require_extension(mut self, name: &str, field: ExtensionField) -> Self {
+        self.push_extension(name, Some(field), true);
+        self
+    }
+
+    pub fn request_extension(mut self, name: &str, field: ExtensionField) -> Self {
+        self.push_extension(name, Some(field), false);
+        self
+    }
+
+    // For extensions without a field in xr::ExtensionSet.
+    pub fn require_other_extension(mut self, name: &str) -> Self {
+        self.push_extension(name, None, true);
+        self
+    }
+
+    pub fn request_other_extension(mut self, name: &str) -> Self {
+        self.push_extension(name, None, false);
+        self
+    }
+
+    pub fn require_layer(mut self, name: &str) -> Self {
+        self.push_layer(name, true);
+        self
+    }
+
+    pub fn request_layer(mut self, name: &str) -> Self {
+        self.push_layer(name, false);
+        self
+    }
+
+    fn push_extension(&mut self, name: &str, field: Option<ExtensionField>, required: bool) {
+        // requesting the same extension twice keeps the stricter of both
+        if let Some(existing) = self.extensions.iter_mut().find(|request| request.name == name) {
+            existing.required |= required;
+            return;
+        }
+        self.extensions.push(ExtensionRequest { name: name.to_string(), field, required });
+    }
+
+    fn push_layer(&mut self, name: &str, required: bool) {
+        if let Some(existing) = self.layers.iter_mut().find(|request| request.name == name) {
+            existing.required |= required;
+            return;
+        }
+        self.layers.push(LayerRequest { name: name.to_string(), required });
+    }
+
+    pub fn build(self) -> xr::Result<OpenXRInstance> {
+        let mut available_extensions = self.entry.enumerate_extensions()?;
+        let available_layers = self.entry.enumerate_layers()?;
+        info!(
+            "OpenXR runtime offers {} API layers: [{}]",
+            available_layers.len(),
+            available_layers.iter().map(|layer| layer.layer_name.as_str()).collect::<Vec<_>>().join(", ")
+        );
+
+        let mut extensions = xr::ExtensionSet::default();
+        let mut enabled_extensions = Vec::new();
+
+        for request in &self.extensions {
+            let available = match request.field {
+                Some(field) => *field(&mut available_extensions),
+                None => available_extensions.other.contains(&request.name),
+            };
+
+            if !available {
+                if request.required {
+                    crit!("Required OpenXR extension {} is not available", request.name);
+                    return Err(xr::sys::Result::ERROR_EXTENSION_NOT_PRESENT);
+                }
+                info!("Optional OpenXR extension {} is not available, skipping", request.name);
+                continue;
+            }
+
+            match request.field {
+                Some(field) => *field(&mut extensions) = true,
+                None => extensions.other.push(request.name.clone()),
+            }
+            enabled_extensions.push(request.name.clone());
+        }
+
+        let mut enabled_layers = Vec::new();
+        for request in &self.layers {
+            if !available_layers.iter().any(|layer| layer.layer_name == request.name) {
+                if request.required {
+                    crit!("Required OpenXR API layer {} is not available", request.name);
+                    return Err(xr::sys::Result::ERROR_API_LAYER_NOT_PRESENT);
+                }
+                info!("Optional OpenXR API layer {} is not available, skipping", request.name);
+                continue;
+            }
+            enabled_layers.push(request.name.clone());
+        }
+
+        let instance = self.entry.create_instance(
+            &xr::ApplicationInfo {
+                application_name: &self.application_name,
+                application_version: self.application_version,
+                engine_name: "Neon Engine",
+                engine_version: 0,
+            },
+            &extensions,
+            &enabled_layers.iter().map(String::as_str).collect::<Vec<_>>(),
+        )?;
+
+        let properties = instance.properties()?;
+        success!(
+            "Created OpenXR instance on {} {}, extensions: [{}], layers: [{}]",
+            properties.runtime_name,
+            properties.runtime_version,
+            enabled_extensions.join(", "),
+            enabled_layers.join(", ")
+        );
+
+        Ok(OpenXRInstance {
+            instance,
+            extensions,
+            enabled_extensions,
+            enabled_layers,
+        })
+    }
+}
+
+
+pub struct OpenXRInstance {
+    pub instance: xr::Instance,
+    extensions: xr::ExtensionSet,
+    enabled_extensions: Vec<String>,
+    enabled_layers: Vec<String>,
+}
+
+impl OpenXRInstance {
+    pub fn enabled_extensions(&self) -> &[String] {
+        &self.enabled_extensions
+    }
+
+    pub fn enabled_layers(&self) -> &[String] {
+        &self.enabled_layers
+    }
+
+    pub fn extension_set(&self) -> &xr::ExtensionSet {
+        &self.extensions
+    }
+
+    pub fn is_extension_enabled(&self, name: &str) -> bool {
+        self.enabled_extensions.iter().any(|enabled| enabled == name)
+    }
+}
@@ -0,0 +1,163 @@
+use openxr as xr;
+use mlog::*;
+
+use crate::platform::openxr::session::OpenXRSession;
+
+pub const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ReferenceSpaceChange {
+    pub space_type: xr::ReferenceSpaceType,
+    pub change_time: xr::Time,
+    pub pose_valid: bool,
+    pub pose_in_previous_space: xr::Posef,
+}
+
+type StateChangedCallback = Box<dyn FnMut(xr::SessionState, xr::SessionState)>;
+type ReferenceSpaceChangeCallback = Box<dyn FnMut(&ReferenceSpaceChange)>;
+type InstanceLossCallback = Box<dyn FnMut(xr::Time)>;
+
+// Drives an OpenXRSession through the OpenXR session lifecycle:
+// IDLE -> READY (begin) -> SYNCHRONIZED -> VISIBLE -> FOCUSED -> STOPPING (end) -> EXITING / LOSS_PENDING.
+pub struct SessionLifecycle {
+    event_storage: xr::EventDataBuffer,
+    state: xr::SessionState,
+    running: bool,
+    exit_requested: bool,
+    quit: bool,
+    on_state_changed: Vec<StateChangedCallback>,
+    on_reference_space_change: Vec<ReferenceSpaceChangeCallback>,
+    on_instance_loss: Vec<InstanceLossCallback>,
+}
+
+impl SessionLifecycle {
+    pub fn new() -> Self {
+        Self {
+            event_storage: xr::EventDataBuffer::new(),
+            state: xr::SessionState::UNKNOWN,
+            running: false,
+            exit_requested: false,
+            quit: false,
+            on_state_changed: Vec::new(),
+            on_reference_space_change: Vec::new(),
+            on_instance_loss: Vec::new(),
+        }
+    }
+
+    // Called with (previous state, new state) after the lifecycle has reacted to the change.
+    pub fn on_state_changed(&mut self, callback: impl FnMut(xr::SessionState, xr::SessionState) + 'static) {
+        self.on_state_changed.push(Box::new(callback));
+    }
+
+    pub fn on_reference_space_change(&mut self, callback: impl FnMut(&ReferenceSpaceChange) + 'static) {
+        self.on_reference_space_change.push(Box::new(callback));
+    }
+
+    // Called with the time at which the instance will be lost, the application should shut down before then.
+    pub fn on_instance_loss(&mut self, callback: impl FnMut(xr::Time) + 'static) {
+        self.on_instance_loss.push(Box::new(callback));
+    }
+
+    pub fn state(&self) -> xr::SessionState {
+        self.state
+    }
+
+    // True between xrBeginSession and xrEndSession, i.e. the frame loop has to run.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.state == xr::SessionState::VISIBLE || self.state == xr::SessionState::FOCUSED
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.state == xr::SessionState::FOCUSED
+    }
+
+    // Asks the runtime to end the session, the lifecycle then walks through STOPPING -> IDLE -> EXITING.
+    // A session that never started running can't be ended by the runtime, so the next poll just stops.
+    pub fn request_exit(&mut self, session: &OpenXRSession) -> xr::Result<()> {
+        if self.exit_requested {
+            return Ok(());
+        }
+        self.exit_requested = true;
+
+        if self.running {
+            session.session.request_exit()
+        } else {
+            self.quit = true;
+            Ok(())
+        }
+    }
+
+    // Drains the OpenXR event queue, beginning / ending the session as required.
+    // Returns false once the application should stop (session exited, instance lost, or exit requested before start).
+    pub fn poll_events(&mut self, xr_instance: &xr::Instance, session: &OpenXRSession) -> xr::Result<bool> {
+        while let Some(event) = xr_instance.poll_event(&mut self.event_storage)? {
+            match event {
+                xr::Event::SessionStateChanged(event) => {
+                    let new_state = event.state();
+                    if !self.handle_state_change(session, new_state)? {
+                        return Ok(false);
+                    }
+                }
+                xr::Event::ReferenceSpaceChangePending(event) => {
+                    let change = ReferenceSpaceChange {
+                        space_type: event.reference_space_type(),
+                        change_time: event.change_time(),
+                        pose_valid: event.pose_valid(),
+                        pose_in_previous_space: event.pose_in_previous_space(),
+                    };
+                    info!("OpenXR reference space change pending: {:?}", change.space_type);
+
+                    for callback in &mut self.on_reference_space_change {
+                        callback(&change);
+                    }
+                }
+                xr::Event::InstanceLossPending(event) => {
+                    crit!("OpenXR instance loss pending");
+                    self.running = false;
+
+                    for callback in &mut self.on_instance_loss {
+                        callback(event.loss_time());
+                    }
+                    return Ok(false);
+                }
+                xr::Event::EventsLost(event) => {
+                    crit!("OpenXR lost {} events", event.lost_event_count());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(!self.quit)
+    }
+
+    fn handle_state_change(&mut self, session: &OpenXRSession, new_state: xr::SessionState) -> xr::Result<bool> {
+        let previous_state = self.state;
+        self.state = new_state;
+        info!("OpenXR session state: {:?} -> {:?}", previous_state, new_state);
+
+        let keep_running = match new_state {
+            xr::SessionState::READY => {
+                session.session.begin(VIEW_TYPE)?;
+                self.running = true;
+                true
+            }
+            xr::SessionState::STOPPING => {
+                session.session.end()?;
+                self.running = false;
+                true
+            }
+            xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => false,
+            _ => true,
+        };
+
+        for callback in &mut self.on_state_changed {
+            callback(previous_state, new_state);
+        }
+
+        Ok(keep_running)
+    }
+}
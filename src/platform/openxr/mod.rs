@@ -1,7 +1,33 @@
+pub mod action_manifest;
 pub mod action_set;
+pub mod default_bindings;
 pub mod device_emulation;
+pub mod frame_loop;
+pub mod hand_emulation;
+pub mod hand_tracking;
+pub mod haptics;
+pub mod input_snapshot;
+pub mod instance_builder;
+pub mod lifecycle;
+pub mod motion;
+pub mod pose_recording;
+pub mod remote_control;
 pub mod session;
+pub mod xr_swapchain;
 
+pub use action_manifest::*;
 pub use action_set::*;
+pub use default_bindings::*;
 pub use device_emulation::*;
-pub use session::*;
\ No newline at end of file
+pub use frame_loop::*;
+pub use hand_emulation::*;
+pub use hand_tracking::*;
+pub use haptics::*;
+pub use input_snapshot::*;
+pub use instance_builder::*;
+pub use lifecycle::*;
+pub use motion::*;
+pub use pose_recording::*;
+pub use remote_control::*;
+pub use session::*;
+pub use xr_swapchain::*;
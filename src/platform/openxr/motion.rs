@@ -0,0 +1,503 @@
+use openxr as xr;
+
+use std::f32::consts::PI;
+
+// Scripted pose source for a VirtualDevice, evaluated once per frame by DeviceManager::update_all_devices.
+// `time` is in seconds on the caller's clock, the same time always has to produce the same pose.
+pub trait MotionSource {
+    fn evaluate(&mut self, time: f64) -> xr::Posef;
+}
+
+
+// Circles `center` in the horizontal plane, optionally turning to keep facing it.
+pub struct Orbit {
+    pub center: xr::Vector3f,
+    pub radius: f32,
+    pub period: f32,  // seconds per revolution
+    pub face_center: bool,
+}
+
+impl MotionSource for Orbit {
+    fn evaluate(&mut self, time: f64) -> xr::Posef {
+        let angle = phase(time, self.period) * 2.0 * PI;
+        let position = xr::Vector3f {
+            x: self.center.x + self.radius * angle.sin(),
+            y: self.center.y,
+            z: self.center.z + self.radius * angle.cos(),
+        };
+
+        // -Z is forward in OpenXR, so yawing by `angle` looks back at the center
+        let orientation = if self.face_center {
+            quat_from_axis_angle(Y_AXIS, angle)
+        } else {
+            IDENTITY_ROTATION
+        };
+
+        xr::Posef { orientation, position }
+    }
+}
+
+
+// Lemniscate of Gerono in the XY plane around `center`, orientation follows the direction of travel.
+pub struct FigureEight {
+    pub center: xr::Vector3f,
+    pub width: f32,
+    pub height: f32,
+    pub period: f32,
+}
+
+impl MotionSource for FigureEight {
+    fn evaluate(&mut self, time: f64) -> xr::Posef {
+        let angle = phase(time, self.period) * 2.0 * PI;
+        let position = xr::Vector3f {
+            x: self.center.x + self.width * 0.5 * angle.sin(),
+            y: self.center.y + self.height * 0.5 * angle.sin() * angle.cos(),
+            z: self.center.z,
+        };
+
+        // derivative of the curve, rolled into the direction of travel
+        let dx = angle.cos();
+        let dy = (2.0 * angle).cos();
+        let roll = dy.atan2(dx);
+
+        xr::Posef {
+            orientation: quat_from_axis_angle(Z_AXIS, roll),
+            position,
+        }
+    }
+}
+
+
+// Moves back and forth between `from` and `to`, one full sweep there and back per period.
+pub struct LinearSweep {
+    pub from: xr::Vector3f,
+    pub to: xr::Vector3f,
+    pub period: f32,
+    pub orientation: xr::Quaternionf,
+}
+
+impl MotionSource for LinearSweep {
+    fn evaluate(&mut self, time: f64) -> xr::Posef {
+        let t = phase(time, self.period);
+        let ping_pong = if t < 0.5 { t * 2.0 } else { 2.0 - t * 2.0 };
+
+        xr::Posef {
+            orientation: self.orientation,
+            position: vec3_lerp(self.from, self.to, ping_pong),
+        }
+    }
+}
+
+
+// Seeded random walk, simulated in fixed steps so the pose only depends on (seed, time).
+// Evaluating an earlier time than the previous call replays the walk from the start.
+pub struct RandomWalk {
+    pub origin: xr::Vector3f,
+    pub speed: f32,  // meters per second
+    pub max_distance: f32,  // the walk is pulled back towards `origin` beyond this
+    seed: u64,
+    rng: u64,
+    position: xr::Vector3f,
+    yaw: f32,
+    steps: u64,
+}
+
+impl RandomWalk {
+    const STEP: f64 = 1.0 / 90.0;
+
+    pub fn new(origin: xr::Vector3f, speed: f32, max_distance: f32, seed: u64) -> Self {
+        Self {
+            origin,
+            speed,
+            max_distance,
+            seed,
+            rng: seed.max(1),
+            position: origin,
+            yaw: 0.0,
+            steps: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.rng = self.seed.max(1);
+        self.position = self.origin;
+        self.yaw = 0.0;
+        self.steps = 0;
+    }
+
+    // xorshift64, good enough for test motion and stable across platforms
+    fn next_random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+    }
+
+    fn step(&mut self) {
+        let step_length = self.speed * Self::STEP as f32;
+        let direction = xr::Vector3f {
+            x: self.next_random(),
+            y: self.next_random() * 0.25,
+            z: self.next_random(),
+        };
+        self.yaw += self.next_random() * 0.05;
+
+        let mut position = vec3_add(self.position, vec3_scale(vec3_normalize(direction), step_length));
+        let offset = vec3_sub(position, self.origin);
+        if vec3_length(offset) > self.max_distance {
+            position = vec3_sub(position, vec3_scale(vec3_normalize(offset), step_length * 2.0));
+        }
+
+        self.position = position;
+        self.steps += 1;
+    }
+}
+
+impl MotionSource for RandomWalk {
+    fn evaluate(&mut self, time: f64) -> xr::Posef {
+        let target_steps = (time.max(0.0) / Self::STEP) as u64;
+        if target_steps < self.steps {
+            self.reset();
+        }
+        while self.steps < target_steps {
+            self.step();
+        }
+
+        xr::Posef {
+            orientation: quat_from_axis_angle(Y_AXIS, self.yaw),
+            position: self.position,
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub pose: xr::Posef,
+}
+
+// Catmull-Rom spline through the keyframe positions, rotations are slerped between neighbouring keyframes.
+pub struct KeyframeSpline {
+    keyframes: Vec<Keyframe>,  // sorted by time, never empty
+    pub looping: bool,
+}
+
+impl KeyframeSpline {
+    // None without keyframes, there is no pose to hold.
+    pub fn new(mut keyframes: Vec<Keyframe>, looping: bool) -> Option<Self> {
+        if keyframes.is_empty() {
+            return None;
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Some(Self { keyframes, looping })
+    }
+
+    fn position_at(&self, index: isize) -> xr::Vector3f {
+        let last = self.keyframes.len() as isize - 1;
+        let index = if self.looping {
+            index.rem_euclid(last + 1)
+        } else {
+            index.clamp(0, last)
+        };
+        self.keyframes[index as usize].pose.position
+    }
+}
+
+impl MotionSource for KeyframeSpline {
+    fn evaluate(&mut self, time: f64) -> xr::Posef {
+        let first = self.keyframes[0];
+        let last = self.keyframes[self.keyframes.len() - 1];
+        let duration = last.time - first.time;
+
+        if self.keyframes.len() == 1 || duration <= 0.0 {
+            return first.pose;
+        }
+
+        let mut t = time as f32;
+        if self.looping {
+            t = first.time + (t - first.time).rem_euclid(duration);
+        } else if t <= first.time {
+            return first.pose;
+        } else if t >= last.time {
+            return last.pose;
+        }
+
+        let segment = self
+            .keyframes
+            .windows(2)
+            .position(|pair| t >= pair[0].time && t <= pair[1].time)
+            .unwrap_or(self.keyframes.len() - 2);
+
+        let start = self.keyframes[segment];
+        let end = self.keyframes[segment + 1];
+        let local_t = (t - start.time) / (end.time - start.time).max(f32::EPSILON);
+
+        let index = segment as isize;
+        let position = catmull_rom(
+            self.position_at(index - 1),
+            start.pose.position,
+            end.pose.position,
+            self.position_at(index + 2),
+            local_t,
+        );
+
+        xr::Posef {
+            orientation: quat_slerp(start.pose.orientation, end.pose.orientation, local_t),
+            position,
+        }
+    }
+}
+
+
+// Shared pose math for the emulation code, OpenXR conventions (right handed, +Y up, -Z forward).
+
+pub const IDENTITY_ROTATION: xr::Quaternionf = xr::Quaternionf { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+pub const X_AXIS: xr::Vector3f = xr::Vector3f { x: 1.0, y: 0.0, z: 0.0 };
+pub const Y_AXIS: xr::Vector3f = xr::Vector3f { x: 0.0, y: 1.0, z: 0.0 };
+pub const Z_AXIS: xr::Vector3f = xr::Vector3f { x: 0.0, y: 0.0, z: 1.0 };
+
+fn phase(time: f64, period: f32) -> f32 {
+    if period <= 0.0 {
+        return 0.0;
+    }
+    (time / period as f64).rem_euclid(1.0) as f32
+}
+
+pub fn vec3_add(a: xr::Vector3f, b: xr::Vector3f) -> xr::Vector3f {
+    xr::Vector3f { x: a.x + b.x, y: a.y + b.y, z: a.z + b.z }
+}
+
+pub fn vec3_sub(a: xr::Vector3f, b: xr::Vector3f) -> xr::Vector3f {
+    xr::Vector3f { x: a.x - b.x, y: a.y - b.y, z: a.z - b.z }
+}
+
+pub fn vec3_scale(v: xr::Vector3f, s: f32) -> xr::Vector3f {
+    xr::Vector3f { x: v.x * s, y: v.y * s, z: v.z * s }
+}
+
+pub fn vec3_length(v: xr::Vector3f) -> f32 {
+    (v.x * v.x + v.y * v.y + v.z * v.z).sqrt()
+}
+
+pub fn vec3_normalize(v: xr::Vector3f) -> xr::Vector3f {
+    let length = vec3_length(v);
+    if length <= f32::EPSILON {
+        xr::Vector3f { x: 0.0, y: 0.0, z: 0.0 }
+    } else {
+        vec3_scale(v, 1.0 / length)
+    }
+}
+
+pub fn vec3_lerp(a: xr::Vector3f, b: xr::Vector3f, t: f32) -> xr::Vector3f {
+    vec3_add(a, vec3_scale(vec3_sub(b, a), t))
+}
+
+fn catmull_rom(p0: xr::Vector3f, p1: xr::Vector3f, p2: xr::Vector3f, p3: xr::Vector3f, t: f32) -> xr::Vector3f {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let component = |a: f32, b: f32, c: f32, d: f32| {
+        0.5 * ((2.0 * b) + (-a + c) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 + (-a + 3.0 * b - 3.0 * c + d) * t3)
+    };
+
+    xr::Vector3f {
+        x: component(p0.x, p1.x, p2.x, p3.x),
+        y: component(p0.y, p1.y, p2.y, p3.y),
+        z: component(p0.z, p1.z, p2.z, p3.z),
+    }
+}
+
+pub fn quat_from_axis_angle(axis: xr::Vector3f, angle: f32) -> xr::Quaternionf {
+    let axis = vec3_normalize(axis);
+    let (sin, cos) = (angle * 0.5).sin_cos();
+    xr::Quaternionf { x: axis.x * sin, y: axis.y * sin, z: axis.z * sin, w: cos }
+}
+
+pub fn quat_mul(a: xr::Quaternionf, b: xr::Quaternionf) -> xr::Quaternionf {
+    xr::Quaternionf {
+        x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+        y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+        z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+    }
+}
+
+pub fn quat_normalize(q: xr::Quaternionf) -> xr::Quaternionf {
+    let length = (q.x * q.x + q.y * q.y + q.z * q.z + q.w * q.w).sqrt();
+    if length <= f32::EPSILON {
+        IDENTITY_ROTATION
+    } else {
+        xr::Quaternionf { x: q.x / length, y: q.y / length, z: q.z / length, w: q.w / length }
+    }
+}
+
+pub fn quat_rotate(q: xr::Quaternionf, v: xr::Vector3f) -> xr::Vector3f {
+    // v' = v + 2w(u x v) + 2u x (u x v)
+    let u = xr::Vector3f { x: q.x, y: q.y, z: q.z };
+    let cross = |a: xr::Vector3f, b: xr::Vector3f| xr::Vector3f {
+        x: a.y * b.z - a.z * b.y,
+        y: a.z * b.x - a.x * b.z,
+        z: a.x * b.y - a.y * b.x,
+    };
+    let uv = cross(u, v);
+    let uuv = cross(u, uv);
+    vec3_add(v, vec3_add(vec3_scale(uv, 2.0 * q.w), vec3_scale(uuv, 2.0)))
+}
+
+pub fn quat_slerp(a: xr::Quaternionf, b: xr::Quaternionf, t: f32) -> xr::Quaternionf {
+    let mut dot = a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+
+    // take the short way around
+    let b = if dot < 0.0 {
+        dot = -dot;
+        xr::Quaternionf { x: -b.x, y: -b.y, z: -b.z, w: -b.w }
+    } else {
+        b
+    };
+
+    let (weight_a, weight_b) = if dot > 0.9995 {
+        (1.0 - t, t)
+    } else {
+        let theta = dot.clamp(-1.0, 1.0).acos();
+        let sin_theta = theta.sin();
+        (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+    };
+
+    quat_normalize(xr::Quaternionf {
+        x: a.x * weight_a + b.x * weight_b,
+        y: a.y * weight_a + b.y * weight_b,
+        z: a.z * weight_a + b.z * weight_b,
+        w: a.w * weight_a + b.w * weight_b,
+    })
+}
+
+// Composes `local` relative to `parent`.
+pub fn pose_mul(parent: xr::Posef, local: xr::Posef) -> xr::Posef {
+    xr::Posef {
+        orientation: quat_normalize(quat_mul(parent.orientation, local.orientation)),
+        position: vec3_add(parent.position, quat_rotate(parent.orientation, local.position)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_pose_near(actual: xr::Posef, expected: xr::Posef) {
+        let position = vec3_length(vec3_sub(actual.position, expected.position));
+        let (a, b) = (actual.orientation, expected.orientation);
+        let dot = (a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w).abs();
+        assert!(
+            position < 1e-4 && dot > 1.0 - 1e-5,
+            "pose {:?} is not near {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn keyframe(time: f32, x: f32, y: f32, yaw: f32) -> Keyframe {
+        Keyframe {
+            time,
+            pose: xr::Posef {
+                orientation: quat_from_axis_angle(Y_AXIS, yaw),
+                position: xr::Vector3f { x, y, z: -1.0 },
+            },
+        }
+    }
+
+    fn keyframes() -> Vec<Keyframe> {
+        // deliberately out of order, `new` sorts them
+        vec![
+            keyframe(2.0, 1.0, 1.5, 0.6),
+            keyframe(0.5, 0.0, 1.0, 0.0),
+            keyframe(1.0, 0.5, 1.2, 0.3),
+            keyframe(3.5, 0.2, 1.1, 0.9),
+        ]
+    }
+
+    #[test]
+    fn random_walk_depends_only_on_seed_and_time() {
+        let origin = xr::Vector3f { x: 0.0, y: 1.5, z: 0.0 };
+        let mut a = RandomWalk::new(origin, 0.5, 1.0, 42);
+        let mut b = RandomWalk::new(origin, 0.5, 1.0, 42);
+
+        let pose = a.evaluate(3.0);
+        assert_eq!(b.evaluate(3.0), pose);
+        assert_ne!(RandomWalk::new(origin, 0.5, 1.0, 7).evaluate(3.0), pose);
+        assert!(vec3_length(vec3_sub(pose.position, origin)) > 0.0);
+    }
+
+    #[test]
+    fn random_walk_replays_earlier_times() {
+        let origin = xr::Vector3f { x: 0.0, y: 1.5, z: 0.0 };
+        let mut walk = RandomWalk::new(origin, 0.5, 1.0, 42);
+        let early = walk.evaluate(1.0);
+
+        walk.evaluate(4.0);
+        assert_eq!(walk.evaluate(1.0), early);
+        assert_eq!(walk.evaluate(0.0).position, origin);
+    }
+
+    #[test]
+    fn keyframe_spline_needs_a_keyframe() {
+        assert!(KeyframeSpline::new(Vec::new(), false).is_none());
+    }
+
+    #[test]
+    fn keyframe_spline_passes_through_every_keyframe() {
+        for looping in [false, true] {
+            let mut spline = KeyframeSpline::new(keyframes(), looping).unwrap();
+            let mut keyframes = keyframes();
+            if looping {
+                keyframes.retain(|keyframe| keyframe.time < 3.5);  // the end of the loop is its start
+            }
+            for keyframe in keyframes {
+                assert_pose_near(spline.evaluate(keyframe.time as f64), keyframe.pose);
+            }
+        }
+    }
+
+    #[test]
+    fn keyframe_spline_clamps_at_the_ends() {
+        let mut spline = KeyframeSpline::new(keyframes(), false).unwrap();
+        let first = keyframe(0.5, 0.0, 1.0, 0.0).pose;
+        let last = keyframe(3.5, 0.2, 1.1, 0.9).pose;
+
+        assert_eq!(spline.evaluate(-10.0), first);
+        assert_eq!(spline.evaluate(0.25), first);
+        assert_eq!(spline.evaluate(3.5), last);
+        assert_eq!(spline.evaluate(100.0), last);
+    }
+
+    #[test]
+    fn keyframe_spline_loops_after_the_last_keyframe() {
+        let mut spline = KeyframeSpline::new(keyframes(), true).unwrap();
+        let duration = 3.0;
+
+        assert_pose_near(spline.evaluate(3.5), keyframe(0.5, 0.0, 1.0, 0.0).pose);
+        for time in [0.6, 1.3, 2.9] {
+            let pose = spline.evaluate(time);
+            assert_pose_near(spline.evaluate(time + duration), pose);
+            assert_pose_near(spline.evaluate(time - duration), pose);
+        }
+    }
+
+    #[test]
+    fn orbit_and_figure_eight_are_periodic() {
+        let center = xr::Vector3f { x: 0.0, y: 1.5, z: -1.0 };
+        let mut orbit = Orbit { center, radius: 0.5, period: 4.0, face_center: true };
+        let mut figure_eight = FigureEight { center, width: 0.6, height: 0.3, period: 2.5 };
+
+        for time in [0.0, 0.7, 1.9, 3.3] {
+            let pose = orbit.evaluate(time);
+            assert_pose_near(orbit.evaluate(time + 4.0), pose);
+            assert_pose_near(orbit.evaluate(time + 12.0), pose);
+            assert!((vec3_length(vec3_sub(pose.position, center)) - 0.5).abs() < 1e-5);
+
+            let pose = figure_eight.evaluate(time);
+            assert_pose_near(figure_eight.evaluate(time + 2.5), pose);
+            assert_pose_near(figure_eight.evaluate(time + 7.5), pose);
+        }
+    }
+}
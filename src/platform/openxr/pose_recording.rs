@@ -0,0 +1,507 @@
+use openxr as xr;
+use serde_json::json;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+use std::time::Duration;
+
+// Binary pose recording format (all values little endian):
+//
+//   magic         4 bytes  "NPOS"
+//   version       u32
+//   device_count  u32
+//   devices       device_count x (u16 name length, utf-8 name)
+//   sample_count  u64
+//   samples       sample_count x (u16 device index, u64 time ns, 7 x f32 px py pz qx qy qz qw)
+//
+// Samples are stored in recording order, which is non-decreasing in time.
+const MAGIC: &[u8; 4] = b"NPOS";
+const VERSION: u32 = 1;
+const SAMPLE_SIZE: usize = 2 + 8 + 7 * 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PoseSample {
+    pub device: u16,  // index into PoseRecording::devices
+    pub time: Duration,  // since the start of the recording
+    pub pose: xr::Posef,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PoseRecording {
+    pub devices: Vec<String>,
+    pub samples: Vec<PoseSample>,
+}
+
+impl PoseRecording {
+    // Fails once the format's u16 device index runs out, instead of wrapping onto another device.
+    pub fn device_index(&mut self, device_name: &str) -> Result<u16> {
+        if let Some(index) = self.devices.iter().position(|name| name == device_name) {
+            return Ok(index as u16);
+        }
+        if self.devices.len() > u16::MAX as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("More than {} devices in one recording", self.devices.len())));
+        }
+
+        self.devices.push(device_name.to_string());
+        Ok((self.devices.len() - 1) as u16)
+    }
+
+    // Samples have to arrive in time order, PoseReplay relies on it.
+    pub fn push(&mut self, device_name: &str, time: Duration, pose: xr::Posef) -> Result<()> {
+        if time < self.duration() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Pose at {:?} recorded after one at {:?}", time, self.duration()),
+            ));
+        }
+        let device = self.device_index(device_name)?;
+        self.samples.push(PoseSample { device, time, pose });
+        Ok(())
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.samples.last().map_or(Duration::ZERO, |sample| sample.time)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_binary(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::read_binary(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        let device_count = u32::try_from(self.devices.len())
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Too many devices"))?;
+        writer.write_all(&device_count.to_le_bytes())?;
+        for name in &self.devices {
+            let name_length = u16::try_from(name.len()).map_err(|_| {
+                Error::new(ErrorKind::InvalidInput, format!("Device name longer than {} bytes", u16::MAX))
+            })?;
+            writer.write_all(&name_length.to_le_bytes())?;
+            writer.write_all(name.as_bytes())?;
+        }
+
+        writer.write_all(&(self.samples.len() as u64).to_le_bytes())?;
+        for sample in &self.samples {
+            writer.write_all(&sample.device.to_le_bytes())?;
+            writer.write_all(&(sample.time.as_nanos() as u64).to_le_bytes())?;
+            for value in pose_to_array(&sample.pose) {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads the whole input first so every count in the header can be checked against the bytes actually left,
+    // a corrupt count then fails cleanly instead of attempting a huge allocation.
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut input = bytes.as_slice();
+
+        if read_array::<4>(&mut input)? != *MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a pose recording"));
+        }
+
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Unsupported pose recording version {}", version)));
+        }
+
+        let device_count = read_u32(&mut input)? as usize;
+        if device_count.saturating_mul(2) > input.len() {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Device count {} exceeds the file size", device_count)));
+        }
+        let mut devices = Vec::new();
+        for _ in 0..device_count {
+            let name_length = read_u16(&mut input)? as usize;
+            if name_length > input.len() {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Device name runs past the end of the file"));
+            }
+            let (name, rest) = input.split_at(name_length);
+            input = rest;
+            devices.push(String::from_utf8(name.to_vec()).map_err(|e| Error::new(ErrorKind::InvalidData, e))?);
+        }
+
+        let sample_count = read_u64(&mut input)?;
+        if sample_count.saturating_mul(SAMPLE_SIZE as u64) > input.len() as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Sample count {} exceeds the file size", sample_count)));
+        }
+        let mut samples = Vec::new();
+        for _ in 0..sample_count {
+            let device = read_u16(&mut input)?;
+            if device as usize >= devices.len() {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Sample references unknown device {}", device)));
+            }
+            let time = Duration::from_nanos(read_u64(&mut input)?);
+            if samples.last().is_some_and(|previous: &PoseSample| time < previous.time) {
+                return Err(Error::new(ErrorKind::InvalidData, format!("Sample at {:?} is out of time order", time)));
+            }
+
+            let mut values = [0.0f32; 7];
+            for value in &mut values {
+                *value = f32::from_le_bytes(read_array(&mut input)?);
+            }
+
+            samples.push(PoseSample { device, time, pose: pose_from_array(values) });
+        }
+
+        Ok(Self { devices, samples })
+    }
+
+    // Non-finite values come out as null, JSON has no NaN / inf.
+    pub fn export_json(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_json(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let samples = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let p = &sample.pose.position;
+                let o = &sample.pose.orientation;
+                json!({
+                    "device": self.devices[sample.device as usize],
+                    "time_ns": sample.time.as_nanos() as u64,
+                    "position": [p.x, p.y, p.z],
+                    "orientation": [o.x, o.y, o.z, o.w],
+                })
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::to_writer_pretty(&mut *writer, &json!({ "devices": self.devices, "samples": samples }))?;
+        writeln!(writer)
+    }
+
+    pub fn export_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(writer, "device,time_ns,px,py,pz,qx,qy,qz,qw")?;
+        for sample in &self.samples {
+            let p = &sample.pose.position;
+            let o = &sample.pose.orientation;
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{}",
+                csv_field(&self.devices[sample.device as usize]),
+                sample.time.as_nanos(),
+                p.x, p.y, p.z,
+                o.x, o.y, o.z, o.w,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Records poses on the caller's clock (e.g. simulated frame time), so recordings are independent of wall time.
+// Timestamps are stored relative to the first recorded pose and must never go backwards.
+pub struct PoseRecorder {
+    start: Option<Duration>,  // caller time of the first recorded pose
+    recording: PoseRecording,
+}
+
+impl PoseRecorder {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            recording: PoseRecording::default(),
+        }
+    }
+
+    pub fn record(&mut self, device_name: &str, time: Duration, pose: xr::Posef) -> Result<()> {
+        let start = *self.start.get_or_insert(time);
+        let time = time.checked_sub(start).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, format!("Pose at {:?} recorded before the recording started", time))
+        })?;
+        self.recording.push(device_name, time, pose)
+    }
+
+    pub fn finish(self) -> PoseRecording {
+        self.recording
+    }
+}
+
+
+// Plays a recording back against a caller supplied clock, so the same sequence of
+// `advance` calls always yields the same poses regardless of real frame timing.
+pub struct PoseReplay {
+    recording: PoseRecording,
+    cursor: usize,
+}
+
+impl PoseReplay {
+    pub fn new(recording: PoseRecording) -> Self {
+        Self { recording, cursor: 0 }
+    }
+
+    pub fn recording(&self) -> &PoseRecording {
+        &self.recording
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.recording.samples.len()
+    }
+
+    pub fn rewind(&mut self) {
+        self.cursor = 0;
+    }
+
+    // Returns every sample with a timestamp <= `elapsed` that hasn't been returned yet, in order.
+    pub fn advance(&mut self, elapsed: Duration) -> Vec<(&str, xr::Posef)> {
+        let start = self.cursor;
+        while self.cursor < self.recording.samples.len() && self.recording.samples[self.cursor].time <= elapsed {
+            self.cursor += 1;
+        }
+
+        self.recording.samples[start..self.cursor]
+            .iter()
+            .map(|sample| (self.recording.devices[sample.device as usize].as_str(), sample.pose))
+            .collect()
+    }
+}
+
+
+fn pose_to_array(pose: &xr::Posef) -> [f32; 7] {
+    [
+        pose.position.x, pose.position.y, pose.position.z,
+        pose.orientation.x, pose.orientation.y, pose.orientation.z, pose.orientation.w,
+    ]
+}
+
+fn pose_from_array(values: [f32; 7]) -> xr::Posef {
+    xr::Posef {
+        position: xr::Vector3f { x: values[0], y: values[1], z: values[2] },
+        orientation: xr::Quaternionf { x: values[3], y: values[4], z: values[5], w: values[6] },
+    }
+}
+
+// RFC 4180: quoted if it contains a separator, quote or line break, quotes doubled.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn read_array<const N: usize>(input: &mut &[u8]) -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u16(input: &mut &[u8]) -> Result<u16> {
+    Ok(u16::from_le_bytes(read_array(input)?))
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_array(input)?))
+}
+
+fn read_u64(input: &mut &[u8]) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_array(input)?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(x: f32) -> xr::Posef {
+        xr::Posef {
+            position: xr::Vector3f { x, y: 1.5, z: -x },
+            orientation: xr::Quaternionf { x: 0.0, y: 0.6, z: 0.0, w: 0.8 },
+        }
+    }
+
+    fn test_recording() -> PoseRecording {
+        let mut recording = PoseRecording::default();
+        recording.push("head", Duration::from_millis(0), pose(0.0)).unwrap();
+        recording.push("right_hand", Duration::from_millis(5), pose(0.5)).unwrap();
+        recording.push("head", Duration::from_millis(11), pose(1.0)).unwrap();
+        recording.push("right_hand", Duration::from_millis(11), pose(1.5)).unwrap();
+        recording.push("head", Duration::from_millis(30), pose(2.0)).unwrap();
+        recording
+    }
+
+    fn to_binary(recording: &PoseRecording) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        recording.write_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let recording = test_recording();
+        let loaded = PoseRecording::read_binary(&mut to_binary(&recording).as_slice()).unwrap();
+
+        assert_eq!(loaded.devices, recording.devices);
+        assert_eq!(loaded.samples.len(), recording.samples.len());
+        for (loaded, original) in loaded.samples.iter().zip(&recording.samples) {
+            assert_eq!(loaded.device, original.device);
+            assert_eq!(loaded.time, original.time);
+            assert_eq!(pose_to_array(&loaded.pose), pose_to_array(&original.pose));
+        }
+    }
+
+    #[test]
+    fn read_rejects_truncated_input() {
+        let bytes = to_binary(&test_recording());
+        for length in [0, 3, 12, bytes.len() - 1] {
+            assert!(PoseRecording::read_binary(&mut &bytes[..length]).is_err(), "accepted {} bytes", length);
+        }
+    }
+
+    #[test]
+    fn read_rejects_counts_larger_than_the_input() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        let error = PoseRecording::read_binary(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        let error = PoseRecording::read_binary(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn write_rejects_device_names_over_u16() {
+        let mut recording = PoseRecording::default();
+        recording.push(&"x".repeat(u16::MAX as usize + 1), Duration::ZERO, pose(0.0)).unwrap();
+        assert!(recording.write_binary(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn device_index_errors_instead_of_wrapping() {
+        let mut recording = PoseRecording {
+            devices: (0..=u16::MAX as usize).map(|index| index.to_string()).collect(),
+            samples: Vec::new(),
+        };
+
+        assert_eq!(recording.device_index("65535").unwrap(), u16::MAX);
+        assert!(recording.device_index("one too many").is_err());
+        assert_eq!(recording.devices.len(), u16::MAX as usize + 1);
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let replay_with_steps = |steps: &[u64]| {
+            let mut replay = PoseReplay::new(test_recording());
+            let mut frames = Vec::new();
+            for &step in steps {
+                let poses = replay.advance(Duration::from_millis(step));
+                frames.push(poses.into_iter().map(|(device, pose)| (device.to_string(), pose_to_array(&pose))).collect::<Vec<_>>());
+            }
+            (frames, replay.is_finished())
+        };
+
+        let steps = [0, 4, 11, 20, 30];
+        let (frames, finished) = replay_with_steps(&steps);
+        assert_eq!(replay_with_steps(&steps), (frames.clone(), finished));
+        assert!(finished);
+
+        // samples at or before each step, in recording order, each delivered exactly once
+        let devices = |frame: &Vec<(String, [f32; 7])>| frame.iter().map(|(device, _)| device.clone()).collect::<Vec<_>>();
+        assert_eq!(devices(&frames[0]), ["head"]);
+        assert!(frames[1].is_empty());
+        assert_eq!(devices(&frames[2]), ["right_hand", "head", "right_hand"]);
+        assert!(frames[3].is_empty());
+        assert_eq!(devices(&frames[4]), ["head"]);
+        assert_eq!(frames[4][0].1, pose_to_array(&pose(2.0)));
+
+        // a different frame rate ends on the same poses
+        let (coarse, _) = replay_with_steps(&[30]);
+        assert_eq!(coarse[0], frames.concat());
+    }
+
+    #[test]
+    fn replay_survives_binary_round_trip() {
+        let loaded = PoseRecording::read_binary(&mut to_binary(&test_recording()).as_slice()).unwrap();
+        let mut original = PoseReplay::new(test_recording());
+        let mut replayed = PoseReplay::new(loaded);
+
+        let owned = |poses: Vec<(&str, xr::Posef)>| {
+            poses.into_iter().map(|(device, pose)| (device.to_string(), pose_to_array(&pose))).collect::<Vec<_>>()
+        };
+        for step in [3, 11, 50] {
+            let expected = owned(original.advance(Duration::from_millis(step)));
+            assert_eq!(owned(replayed.advance(Duration::from_millis(step))), expected);
+        }
+    }
+
+    #[test]
+    fn push_rejects_decreasing_time() {
+        let mut recording = test_recording();
+        assert!(recording.push("head", Duration::from_millis(29), pose(0.0)).is_err());
+        assert!(recording.push("head", Duration::from_millis(30), pose(0.0)).is_ok());
+        assert_eq!(recording.samples.len(), 6);
+    }
+
+    #[test]
+    fn read_rejects_decreasing_time() {
+        let mut recording = test_recording();
+        recording.samples.swap(1, 2);
+        let error = PoseRecording::read_binary(&mut to_binary(&recording).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn recorder_is_relative_to_the_first_pose() {
+        let mut recorder = PoseRecorder::new();
+        recorder.record("head", Duration::from_secs(60), pose(0.0)).unwrap();
+        recorder.record("head", Duration::from_millis(60_011), pose(1.0)).unwrap();
+        assert!(recorder.record("head", Duration::from_secs(59), pose(2.0)).is_err());
+
+        let times = recorder.finish().samples.iter().map(|sample| sample.time).collect::<Vec<_>>();
+        assert_eq!(times, [Duration::ZERO, Duration::from_millis(11)]);
+    }
+
+    #[test]
+    fn csv_quotes_names_with_separators() {
+        let mut recording = PoseRecording::default();
+        recording.push("hand, \"left\"", Duration::ZERO, pose(0.0)).unwrap();
+
+        let mut csv = Vec::new();
+        recording.write_csv(&mut csv).unwrap();
+        let line = String::from_utf8(csv).unwrap().lines().nth(1).unwrap().to_string();
+        assert!(line.starts_with("\"hand, \"\"left\"\"\",0,"), "{}", line);
+    }
+
+    #[test]
+    fn json_is_valid_with_control_characters_and_non_finite_values() {
+        let mut recording = PoseRecording::default();
+        let mut bad_pose = pose(0.0);
+        bad_pose.position.x = f32::NAN;
+        bad_pose.position.y = f32::INFINITY;
+        recording.push("tab\there \"quoted\"\u{1}", Duration::from_nanos(7), bad_pose).unwrap();
+
+        let mut json = Vec::new();
+        recording.write_json(&mut json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        assert_eq!(value["devices"][0], "tab\there \"quoted\"\u{1}");
+        assert_eq!(value["samples"][0]["device"], "tab\there \"quoted\"\u{1}");
+        assert_eq!(value["samples"][0]["time_ns"], 7);
+        assert!(value["samples"][0]["position"][0].is_null());
+        assert!(value["samples"][0]["position"][1].is_null());
+    }
+}
@@ -0,0 +1,379 @@
+use openxr as xr;
+use mlog::*;
+
+use std::io::{Error, ErrorKind, Result};
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use crate::platform::openxr::device_emulation::DeviceManager;
+
+// Remote control of emulated devices over UDP on localhost.
+//
+// Every datagram carries one or more newline separated UTF-8 commands, fields separated by whitespace:
+//
+//   pose   <device> <px> <py> <pz> <qx> <qy> <qz> <qw>    position in meters, orientation as a unit quaternion
+//   button <device> <button> <0|1>                        e.g. "button right_hand trigger 1"
+//   axis   <device> <axis> <value>                        e.g. "axis left_hand thumbstick_x -0.5"
+//   release <device>                                      hands the pose back to local input
+//
+// Numbers must be finite, the quaternion is applied as sent. A pose command marks the device as remote controlled,
+// local desktop input stops moving it until it is released.
+// The server answers each datagram with "ok" or "error <reason>" for the first failing command,
+// so clients can fire and forget or wait for the reply. Datagrams over MAX_DATAGRAM_SIZE bytes are rejected
+// as a whole. From Python:
+//
+//   sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+//   sock.sendto(b"pose right_hand 0.2 1.2 -0.4 0 0 0 1", ("127.0.0.1", 47420))
+pub const DEFAULT_REMOTE_CONTROL_PORT: u16 = 47420;
+
+const MAX_DATAGRAM_SIZE: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteCommand {
+    Pose { device: String, pose: xr::Posef },
+    Button { device: String, button: String, pressed: bool },
+    Axis { device: String, axis: String, value: f32 },
+    Release { device: String },
+}
+
+impl RemoteCommand {
+    pub fn parse(line: &str) -> std::result::Result<Self, String> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        // f32::from_str accepts "NaN" and "inf", which would end up in a device pose
+        let parse_f32 = |field: &str| match field.parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("invalid number '{}'", field)),
+        };
+
+        match fields.as_slice() {
+            ["pose", device, values @ ..] => {
+                if values.len() != 7 {
+                    return Err(format!("pose expects 7 values, got {}", values.len()));
+                }
+                let v = values.iter().map(|value| parse_f32(value)).collect::<std::result::Result<Vec<_>, _>>()?;
+                Ok(RemoteCommand::Pose {
+                    device: device.to_string(),
+                    pose: xr::Posef {
+                        position: xr::Vector3f { x: v[0], y: v[1], z: v[2] },
+                        orientation: xr::Quaternionf { x: v[3], y: v[4], z: v[5], w: v[6] },
+                    },
+                })
+            }
+            ["button", device, button, state] => Ok(RemoteCommand::Button {
+                device: device.to_string(),
+                button: button.to_string(),
+                pressed: match *state {
+                    "1" | "true" | "down" => true,
+                    "0" | "false" | "up" => false,
+                    other => return Err(format!("invalid button state '{}'", other)),
+                },
+            }),
+            ["axis", device, axis, value] => Ok(RemoteCommand::Axis {
+                device: device.to_string(),
+                axis: axis.to_string(),
+                value: parse_f32(value)?,
+            }),
+            ["release", device] => Ok(RemoteCommand::Release { device: device.to_string() }),
+            [command, ..] => Err(format!("unknown or malformed command '{}'", command)),
+            [] => Err("empty command".to_string()),
+        }
+    }
+
+    pub fn to_message(&self) -> String {
+        match self {
+            RemoteCommand::Pose { device, pose } => format!(
+                "pose {} {} {} {} {} {} {} {}",
+                device,
+                pose.position.x, pose.position.y, pose.position.z,
+                pose.orientation.x, pose.orientation.y, pose.orientation.z, pose.orientation.w,
+            ),
+            RemoteCommand::Button { device, button, pressed } => {
+                format!("button {} {} {}", device, button, *pressed as u8)
+            }
+            RemoteCommand::Axis { device, axis, value } => format!("axis {} {} {}", device, axis, value),
+            RemoteCommand::Release { device } => format!("release {}", device),
+        }
+    }
+
+    // `time` stamps pose updates while the device manager is recording, see DeviceManager::update_pose.
+    pub fn apply(&self, device_manager: &mut DeviceManager, time: Duration) -> std::result::Result<(), String> {
+        match self {
+            RemoteCommand::Pose { device, pose } => {
+                device_manager
+                    .update_pose(device, *pose, time)
+                    .map_err(|_| format!("unknown device '{}'", device))?;
+                device_manager.device_mut(device).unwrap().remote_controlled = true;
+                Ok(())
+            }
+            RemoteCommand::Button { device, button, pressed } => {
+                let target = device_manager
+                    .device_mut(device)
+                    .ok_or_else(|| format!("unknown device '{}'", device))?;
+                target.set_button(button, *pressed);
+                Ok(())
+            }
+            RemoteCommand::Axis { device, axis, value } => {
+                let target = device_manager
+                    .device_mut(device)
+                    .ok_or_else(|| format!("unknown device '{}'", device))?;
+                target.set_axis(axis, *value);
+                Ok(())
+            }
+            RemoteCommand::Release { device } => {
+                let target = device_manager
+                    .device_mut(device)
+                    .ok_or_else(|| format!("unknown device '{}'", device))?;
+                target.remote_controlled = false;
+                Ok(())
+            }
+        }
+    }
+}
+
+
+// Non-blocking server, `poll` is meant to be called once per frame from the thread owning the DeviceManager.
+pub struct RemoteControlServer {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl RemoteControlServer {
+    pub fn bind_localhost(port: u16) -> Result<Self> {
+        Self::bind((Ipv4Addr::LOCALHOST, port))
+    }
+
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        info!("Device remote control listening on udp://{}", socket.local_addr()?);
+
+        Ok(Self {
+            socket,
+            buffer: vec![0u8; MAX_DATAGRAM_SIZE + 1],  // one spare byte to detect oversized datagrams
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // Applies every pending datagram to `device_manager` at `time` (the caller's frame time), returns the number
+    // of commands applied.
+    pub fn poll(&mut self, device_manager: &mut DeviceManager, time: Duration) -> usize {
+        let mut applied = 0;
+
+        loop {
+            let (size, sender) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    crit!("Remote control socket error: {}", e);
+                    break;
+                }
+            };
+
+            // the OS silently truncates datagrams to the buffer, parsing the rest would apply half a batch
+            if size > MAX_DATAGRAM_SIZE {
+                crit!("Rejected remote control datagram from {}: larger than {} bytes", sender, MAX_DATAGRAM_SIZE);
+                let reply = format!("error datagram exceeds {} bytes", MAX_DATAGRAM_SIZE);
+                let _ = self.socket.send_to(reply.as_bytes(), sender);
+                continue;
+            }
+
+            let reply = match std::str::from_utf8(&self.buffer[..size]) {
+                Ok(message) => {
+                    let mut reply = "ok".to_string();
+                    for line in message.lines().filter(|line| !line.trim().is_empty()) {
+                        match RemoteCommand::parse(line).and_then(|command| command.apply(device_manager, time)) {
+                            Ok(()) => applied += 1,
+                            Err(reason) => {
+                                info!("Rejected remote command '{}': {}", line, reason);
+                                reply = format!("error {}", reason);
+                                break;
+                            }
+                        }
+                    }
+                    reply
+                }
+                Err(_) => "error message is not valid utf-8".to_string(),
+            };
+
+            let _ = self.socket.send_to(reply.as_bytes(), sender);
+        }
+
+        applied
+    }
+}
+
+
+// Minimal client for test harnesses and tools written in Rust.
+pub struct RemoteClient {
+    socket: UdpSocket,
+}
+
+impl RemoteClient {
+    pub fn connect(server: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        socket.connect(server)?;
+        Ok(Self { socket })
+    }
+
+    pub fn connect_localhost(port: u16) -> Result<Self> {
+        Self::connect((Ipv4Addr::LOCALHOST, port))
+    }
+
+    // Fire and forget, the server's reply is discarded by the next `send_acked`.
+    pub fn send(&self, command: &RemoteCommand) -> Result<()> {
+        self.socket.send(command.to_message().as_bytes())?;
+        Ok(())
+    }
+
+    // Sends a command and waits for the server to apply it (the server only replies from its poll).
+    pub fn send_acked(&self, command: &RemoteCommand, timeout: Duration) -> Result<()> {
+        self.drain_replies()?;
+        self.send(command)?;
+
+        self.socket.set_read_timeout(Some(timeout))?;
+        let mut buffer = [0u8; 512];
+        let size = self.socket.recv(&mut buffer)?;
+        let reply = String::from_utf8_lossy(&buffer[..size]);
+
+        match reply.strip_prefix("error ") {
+            Some(reason) => Err(Error::new(ErrorKind::InvalidInput, reason.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn send_pose(&self, device: &str, pose: xr::Posef) -> Result<()> {
+        self.send(&RemoteCommand::Pose { device: device.to_string(), pose })
+    }
+
+    pub fn send_button(&self, device: &str, button: &str, pressed: bool) -> Result<()> {
+        self.send(&RemoteCommand::Button { device: device.to_string(), button: button.to_string(), pressed })
+    }
+
+    pub fn send_axis(&self, device: &str, axis: &str, value: f32) -> Result<()> {
+        self.send(&RemoteCommand::Axis { device: device.to_string(), axis: axis.to_string(), value })
+    }
+
+    pub fn send_release(&self, device: &str) -> Result<()> {
+        self.send(&RemoteCommand::Release { device: device.to_string() })
+    }
+
+    fn drain_replies(&self) -> Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let mut buffer = [0u8; 512];
+        while self.socket.recv(&mut buffer).is_ok() {}
+        self.socket.set_nonblocking(false)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::openxr::device_emulation::VirtualDevice;
+
+    use std::thread;
+    use std::time::Instant;
+
+    fn pose(x: f32, y: f32, z: f32) -> xr::Posef {
+        xr::Posef {
+            position: xr::Vector3f { x, y, z },
+            orientation: xr::Quaternionf { x: 0.0, y: 0.0, z: 0.0, w: 1.0 },
+        }
+    }
+
+    #[test]
+    fn parses_every_command() {
+        assert_eq!(
+            RemoteCommand::parse("pose right_hand 0.2 1.2 -0.4 0 0 0 1"),
+            Ok(RemoteCommand::Pose { device: "right_hand".to_string(), pose: pose(0.2, 1.2, -0.4) })
+        );
+        assert_eq!(
+            RemoteCommand::parse("button right_hand trigger 1"),
+            Ok(RemoteCommand::Button { device: "right_hand".to_string(), button: "trigger".to_string(), pressed: true })
+        );
+        assert_eq!(
+            RemoteCommand::parse("button left_hand grip up"),
+            Ok(RemoteCommand::Button { device: "left_hand".to_string(), button: "grip".to_string(), pressed: false })
+        );
+        assert_eq!(
+            RemoteCommand::parse("  axis left_hand thumbstick_x -0.5  "),
+            Ok(RemoteCommand::Axis { device: "left_hand".to_string(), axis: "thumbstick_x".to_string(), value: -0.5 })
+        );
+        assert_eq!(
+            RemoteCommand::parse("release head"),
+            Ok(RemoteCommand::Release { device: "head".to_string() })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_commands() {
+        let malformed = [
+            "",
+            "teleport head",
+            "pose right_hand 0 1 2",
+            "pose right_hand 0 1 2 0 0 0 1 5",
+            "pose right_hand 0 1 x 0 0 0 1",
+            "pose right_hand 0 NaN 0 0 0 0 1",
+            "pose right_hand 0 1 0 0 0 0 inf",
+            "button right_hand trigger",
+            "button right_hand trigger maybe",
+            "axis left_hand thumbstick_x",
+            "axis left_hand thumbstick_x fast",
+            "axis left_hand thumbstick_x -inf",
+            "release",
+            "release head now",
+        ];
+        for line in malformed {
+            assert!(RemoteCommand::parse(line).is_err(), "'{}' parsed", line);
+        }
+    }
+
+    #[test]
+    fn messages_parse_back_to_the_same_command() {
+        let commands = [
+            RemoteCommand::Pose { device: "head".to_string(), pose: pose(-0.125, 1.75, 3.5) },
+            RemoteCommand::Button { device: "left_hand".to_string(), button: "menu".to_string(), pressed: true },
+            RemoteCommand::Button { device: "left_hand".to_string(), button: "menu".to_string(), pressed: false },
+            RemoteCommand::Axis { device: "right_hand".to_string(), axis: "trigger".to_string(), value: 0.3 },
+            RemoteCommand::Release { device: "right_hand".to_string() },
+        ];
+        for command in commands {
+            assert_eq!(RemoteCommand::parse(&command.to_message()), Ok(command));
+        }
+    }
+
+    #[test]
+    fn acked_commands_are_applied_by_poll() {
+        let mut server = RemoteControlServer::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = server.local_addr().unwrap();
+
+        let mut devices = DeviceManager::new();
+        devices.add_device(VirtualDevice::detached("right_hand"));
+
+        let client = thread::spawn(move || {
+            let client = RemoteClient::connect(address).unwrap();
+            let timeout = Duration::from_secs(5);
+            let applied = client.send_acked(
+                &RemoteCommand::Button { device: "right_hand".to_string(), button: "trigger".to_string(), pressed: true },
+                timeout,
+            );
+            let rejected = client.send_acked(&RemoteCommand::Release { device: "left_hand".to_string() }, timeout);
+            (applied, rejected)
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !client.is_finished() && Instant::now() < deadline {
+            server.poll(&mut devices, Duration::ZERO);
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let (applied, rejected) = client.join().unwrap();
+        assert!(applied.is_ok());
+        assert_eq!(rejected.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(devices.device("right_hand").unwrap().button("trigger"));
+    }
+}
@@ -0,0 +1,318 @@
+use ash::vk;
+use ash::vk::Handle;
+use openxr as xr;
+use mlog::*;
+
+use crate::platform::openxr::lifecycle::VIEW_TYPE;
+use crate::platform::openxr::session::OpenXRSession;
+use crate::platform::vulkan::context::{vulkan_failure, VulkanContext, COLOR_FORMAT, VIEW_COUNT};
+use crate::platform::vulkan::memory::AllocatedImage;
+use crate::platform::vulkan::resource::{OwnedFramebuffer, OwnedImageView};
+use crate::platform::vulkan::{renderpass, utils};
+
+// Where the multiview depth attachment comes from.
+pub enum DepthTarget {
+    // XR_KHR_composition_layer_depth: a depth swapchain submitted with the projection layer for reprojection
+    Swapchain(xr::Swapchain<xr::Vulkan>),
+    // engine owned depth image per color swapchain image, never seen by the runtime
+    Local(Vec<AllocatedImage>),
+    // one transient multisample depth image shared by all frames, discarded after the pass
+    Multisampled(AllocatedImage),
+}
+
+// OpenXR swapchain of VIEW_COUNT layered images, one layer per eye, rendered in a single multiview pass.
+// Owns 2D array color / depth views and a framebuffer per (color image, depth image) pair, dropped before the images.
+pub struct XrSwapchain {
+    framebuffers: Vec<OwnedFramebuffer>,  // [image_index * depth_stride + depth_index]
+    pub image_views: Vec<OwnedImageView>,
+    pub depth_views: Vec<OwnedImageView>,
+    msaa_view: Option<OwnedImageView>,
+    pub depth: DepthTarget,
+    msaa_image: Option<AllocatedImage>,  // transient multisample color, resolved into the swapchain image
+    pub handle: xr::Swapchain<xr::Vulkan>,
+    pub format: vk::Format,
+    pub depth_format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub images: Vec<vk::Image>,
+    depth_stride: usize,
+}
+
+impl XrSwapchain {
+    pub fn new(
+        xr_instance: &xr::Instance,
+        xr_system: xr::SystemId,
+        session: &OpenXRSession,
+        vk_context: &VulkanContext,
+    ) -> xr::Result<Self> {
+        let format = Self::negotiate_format(session)?;
+        let extent = Self::recommended_extent(xr_instance, xr_system)?;
+        let config = &vk_context.config;
+        let depth_format = config.depth_format;
+
+        let handle = Self::create_xr_swapchain(
+            session,
+            format,
+            extent,
+            xr::SwapchainUsageFlags::COLOR_ATTACHMENT | xr::SwapchainUsageFlags::SAMPLED,
+        )?;
+        let images = Self::enumerate_images(&handle)?;
+
+        info!("Created OpenXR swapchain: {}x{} x {} layers, {:?}, {} images",
+            extent.width, extent.height, VIEW_COUNT, format, images.len());
+
+        // multisample depth can't be resolved on 1.1, so there is nothing single sampled to submit with MSAA
+        let depth_supported = xr_instance.exts().khr_composition_layer_depth.is_some()
+            && Self::supports_format(session, depth_format)?;
+        let submit_depth = depth_supported && !config.is_multisampled();
+
+        let (depth, depth_images) = if config.is_multisampled() {
+            if depth_supported {
+                crit!("{:?} MSAA disables XR_KHR_composition_layer_depth submission, the compositor gets no depth \
+                    for reprojection. Set RenderConfig::msaa_samples to TYPE_1 to submit depth.", config.msaa_samples);
+            } else {
+                info!("{:?} MSAA, depth is not submitted to the compositor", config.msaa_samples);
+            }
+            let depth_image = vk_context.allocator.create_transient_attachment(
+                depth_format,
+                extent,
+                VIEW_COUNT,
+                config.msaa_samples,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                "xr msaa depth image",
+            );
+            let depth_images = vec![depth_image.image];
+            (DepthTarget::Multisampled(depth_image), depth_images)
+        } else if submit_depth {
+            let depth_handle = Self::create_xr_swapchain(
+                session,
+                depth_format,
+                extent,
+                xr::SwapchainUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            )?;
+            let depth_images = Self::enumerate_images(&depth_handle)?;
+            info!("Created OpenXR depth swapchain: {:?}, {} images, submitted for reprojection",
+                depth_format, depth_images.len());
+            (DepthTarget::Swapchain(depth_handle), depth_images)
+        } else {
+            info!("XR_KHR_composition_layer_depth unavailable, depth stays local");
+            let local = (0..images.len())
+                .map(|index| {
+                    vk_context.allocator.create_render_target(
+                        depth_format,
+                        extent,
+                        VIEW_COUNT,
+                        vk::SampleCountFlags::TYPE_1,
+                        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                        &format!("xr depth image {}", index),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let depth_images = local.iter().map(|image| image.image).collect::<Vec<_>>();
+            (DepthTarget::Local(local), depth_images)
+        };
+
+        let msaa_image = config.is_multisampled().then(|| {
+            vk_context.allocator.create_transient_attachment(
+                format,
+                extent,
+                VIEW_COUNT,
+                config.msaa_samples,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                "xr msaa image",
+            )
+        });
+
+        let device = &vk_context.device;
+        unsafe {
+            let create_view = |image: vk::Image,
+                               format: vk::Format,
+                               range: vk::ImageSubresourceRange,
+                               name: String|
+             -> xr::Result<OwnedImageView> {
+                let image_view = device
+                    .create_image_view(
+                        &vk::ImageViewCreateInfo::default()
+                            .image(image)
+                            .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                            .format(format)
+                            .subresource_range(range),
+                        None,
+                    )
+                    .map_err(|e| vulkan_failure("create swapchain image view", e))?;
+                Ok(device.own(image_view, name))
+            };
+
+            let image_views = images
+                .iter()
+                .enumerate()
+                .map(|(index, &image)| {
+                    create_view(image, format, utils::color_subresource_range(VIEW_COUNT), format!("xr swapchain image view {}", index))
+                })
+                .collect::<xr::Result<Vec<_>>>()?;
+
+            let depth_views = depth_images
+                .iter()
+                .enumerate()
+                .map(|(index, &image)| {
+                    create_view(image, depth_format, utils::depth_subresource_range(VIEW_COUNT), format!("xr depth view {}", index))
+                })
+                .collect::<xr::Result<Vec<_>>>()?;
+
+            let msaa_view = msaa_image
+                .as_ref()
+                .map(|msaa_image| {
+                    create_view(msaa_image.image, format, utils::color_subresource_range(VIEW_COUNT), "xr msaa view".to_string())
+                })
+                .transpose()?;
+
+            // The runtime acquires color and depth images independently, so every pairing needs a framebuffer.
+            // Local depth is tied to its color image and multisample depth is shared, one framebuffer each.
+            let depth_stride = if submit_depth { depth_views.len() } else { 1 };
+            let pairs = image_views
+                .iter()
+                .enumerate()
+                .flat_map(|(image_index, image_view)| {
+                    let depth_range = match &depth {
+                        DepthTarget::Swapchain(_) => 0..depth_views.len(),
+                        DepthTarget::Local(_) => image_index..image_index + 1,
+                        DepthTarget::Multisampled(_) => 0..1,
+                    };
+                    depth_range.map(move |depth_index| (image_index, image_view, depth_index))
+                })
+                .collect::<Vec<_>>();
+
+            // multiview framebuffers have a single layer, the render pass view mask selects the array layers
+            let framebuffers = pairs
+                .into_iter()
+                .map(|(image_index, image_view, depth_index)| -> xr::Result<OwnedFramebuffer> {
+                    let framebuffer = device
+                        .create_framebuffer(
+                            &vk::FramebufferCreateInfo::default()
+                                .render_pass(vk_context.render_pass.handle())
+                                .attachments(&renderpass::multiview_attachments(
+                                    image_view.handle(),
+                                    depth_views[depth_index].handle(),
+                                    msaa_view.as_ref().map(|msaa_view| msaa_view.handle()),
+                                ))
+                                .width(extent.width)
+                                .height(extent.height)
+                                .layers(1),
+                            None,
+                        )
+                        .map_err(|e| vulkan_failure("create swapchain framebuffer", e))?;
+                    Ok(device.own(framebuffer, format!("xr swapchain framebuffer {}/{}", image_index, depth_index)))
+                })
+                .collect::<xr::Result<Vec<_>>>()?;
+
+            Ok(Self {
+                framebuffers,
+                image_views,
+                depth_views,
+                msaa_view,
+                depth,
+                msaa_image,
+                handle,
+                format,
+                depth_format,
+                extent,
+                images,
+                depth_stride,
+            })
+        }
+    }
+
+    fn create_xr_swapchain(
+        session: &OpenXRSession,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        usage_flags: xr::SwapchainUsageFlags,
+    ) -> xr::Result<xr::Swapchain<xr::Vulkan>> {
+        session.session.create_swapchain(&xr::SwapchainCreateInfo {
+            create_flags: xr::SwapchainCreateFlags::EMPTY,
+            usage_flags,
+            format: format.as_raw() as _,
+            sample_count: 1,
+            width: extent.width,
+            height: extent.height,
+            face_count: 1,
+            array_size: VIEW_COUNT,
+            mip_count: 1,
+        })
+    }
+
+    fn enumerate_images(handle: &xr::Swapchain<xr::Vulkan>) -> xr::Result<Vec<vk::Image>> {
+        Ok(handle
+            .enumerate_images()?
+            .into_iter()
+            .map(vk::Image::from_raw)
+            .collect())
+    }
+
+    fn supports_format(session: &OpenXRSession, format: vk::Format) -> xr::Result<bool> {
+        Ok(session
+            .session
+            .enumerate_swapchain_formats()?
+            .into_iter()
+            .any(|supported| vk::Format::from_raw(supported as i32) == format))
+    }
+
+    // Depth index is ignored (pass 0) unless the depth target is a swapchain.
+    pub fn framebuffer(&self, image_index: usize, depth_index: usize) -> vk::Framebuffer {
+        self.framebuffers[image_index * self.depth_stride + depth_index].handle()
+    }
+
+    pub fn depth_swapchain(&self) -> Option<&xr::Swapchain<xr::Vulkan>> {
+        match &self.depth {
+            DepthTarget::Swapchain(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    pub fn depth_swapchain_mut(&mut self) -> Option<&mut xr::Swapchain<xr::Vulkan>> {
+        match &mut self.depth {
+            DepthTarget::Swapchain(handle) => Some(handle),
+            _ => None,
+        }
+    }
+
+    // The render pass and pipeline are built for COLOR_FORMAT, so the runtime has to support it exactly.
+    fn negotiate_format(session: &OpenXRSession) -> xr::Result<vk::Format> {
+        let formats = session
+            .session
+            .enumerate_swapchain_formats()?
+            .into_iter()
+            .map(|format| vk::Format::from_raw(format as i32))
+            .collect::<Vec<_>>();
+
+        if formats.contains(&COLOR_FORMAT) {
+            Ok(COLOR_FORMAT)
+        } else {
+            crit!("OpenXR runtime doesn't support swapchain format {:?}, available: {:?}", COLOR_FORMAT, formats);
+            Err(xr::sys::Result::ERROR_SWAPCHAIN_FORMAT_UNSUPPORTED)
+        }
+    }
+
+    // All views share one array image, so it has to fit the largest recommended view.
+    fn recommended_extent(xr_instance: &xr::Instance, xr_system: xr::SystemId) -> xr::Result<vk::Extent2D> {
+        let views = xr_instance.enumerate_view_configuration_views(xr_system, VIEW_TYPE)?;
+        if views.len() != VIEW_COUNT as usize {
+            crit!("Expected {} views for {:?}, runtime reports {}", VIEW_COUNT, VIEW_TYPE, views.len());
+            return Err(xr::sys::Result::ERROR_VIEW_CONFIGURATION_TYPE_UNSUPPORTED);
+        }
+
+        Ok(views.iter().fold(vk::Extent2D { width: 0, height: 0 }, |extent, view| vk::Extent2D {
+            width: extent.width.max(view.recommended_image_rect_width),
+            height: extent.height.max(view.recommended_image_rect_height),
+        }))
+    }
+
+    pub fn image_rect(&self) -> xr::Rect2Di {
+        xr::Rect2Di {
+            offset: xr::Offset2Di { x: 0, y: 0 },
+            extent: xr::Extent2Di {
+                width: self.extent.width as i32,
+                height: self.extent.height as i32,
+            },
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use ash::vk;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use super::resource::{OwnedCommandPool, OwnedDevice, TrackedDevice};
+use super::sync::VulkanSyncObjects;
+
+// Render pass state secondary command buffers continue.
+#[derive(Clone, Copy, Debug)]
+pub struct SecondaryInheritance {
+    pub render_pass: vk::RenderPass,
+    pub subpass: u32,
+    pub framebuffer: vk::Framebuffer,
+}
+
+struct FramePool {
+    pool: OwnedCommandPool,  // buffers are freed with the pool
+    primary: Vec<vk::CommandBuffer>,
+    secondary: Vec<vk::CommandBuffer>,
+    next_primary: usize,
+    next_secondary: usize,
+}
+
+impl FramePool {
+    fn next(&mut self, device: &ash::Device, level: vk::CommandBufferLevel) -> vk::CommandBuffer {
+        let (buffers, next) = match level {
+            vk::CommandBufferLevel::SECONDARY => (&mut self.secondary, &mut self.next_secondary),
+            _ => (&mut self.primary, &mut self.next_primary),
+        };
+
+        // buffers are kept across resets and handed out again, only grow when a frame needs more than before
+        if *next == buffers.len() {
+            let command_buffer = unsafe {
+                device
+                    .allocate_command_buffers(
+                        &vk::CommandBufferAllocateInfo::default()
+                            .command_pool(self.pool.handle())
+                            .level(level)
+                            .command_buffer_count(1),
+                    )
+                    .expect("Failed to allocate command buffer")[0]
+            };
+            buffers.push(command_buffer);
+        }
+
+        *next += 1;
+        buffers[*next - 1]
+    }
+}
+
+fn begin_secondary_buffer(
+    device: &ash::Device,
+    frame_pool: &mut FramePool,
+    inheritance: &SecondaryInheritance,
+) -> vk::CommandBuffer {
+    let command_buffer = frame_pool.next(device, vk::CommandBufferLevel::SECONDARY);
+    let inheritance_info = vk::CommandBufferInheritanceInfo::default()
+        .render_pass(inheritance.render_pass)
+        .subpass(inheritance.subpass)
+        .framebuffer(inheritance.framebuffer);
+
+    unsafe {
+        device
+            .begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(
+                        vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                            | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                    )
+                    .inheritance_info(&inheritance_info),
+            )
+            .expect("Failed to begin secondary command buffer");
+    }
+    command_buffer
+}
+
+type SecondaryRecorder = Arc<dyn Fn(usize, vk::CommandBuffer) + Send + Sync>;
+
+// One secondary buffer for a recording thread to record, everything it needs is owned.
+struct RecordingJob {
+    job: usize,
+    frame: usize,
+    inheritance: SecondaryInheritance,
+    record: SecondaryRecorder,
+    results: Sender<(usize, thread::Result<vk::CommandBuffer>)>,
+}
+
+impl RecordingJob {
+    // Runs on the recording thread that owns `pools`, which stay locked until the buffer is ended.
+    fn run(self, device: &ash::Device, pools: &Mutex<Vec<FramePool>>) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut pools = pools.lock().unwrap();
+            let command_buffer = begin_secondary_buffer(device, &mut pools[self.frame], &self.inheritance);
+            (self.record)(self.job, command_buffer);
+            unsafe {
+                device
+                    .end_command_buffer(command_buffer)
+                    .expect("Failed to record secondary command buffer");
+            }
+            command_buffer
+        }));
+        let _ = self.results.send((self.job, result));
+    }
+}
+
+// Transient command pools, one per (worker, frame in flight). Worker 0 belongs to the render thread, every other
+// worker has a recording thread started with the manager that `record_parallel` hands jobs to; a worker must only
+// be used by one thread at a time since Vulkan requires external synchronization of a pool while recording into
+// its buffers.
+// All buffers of a frame slot are recycled by `begin_frame`, so everything is recorded anew every frame.
+pub struct CommandPoolManager {
+    device: Arc<TrackedDevice>,
+    workers: Vec<Arc<Mutex<Vec<FramePool>>>>,  // [worker][frame]
+    recorders: Vec<Sender<RecordingJob>>,  // recorders[i] records with worker i + 1
+    recorder_threads: Vec<JoinHandle<()>>,
+    current_frame: usize,
+}
+
+impl CommandPoolManager {
+    pub fn new(device: &OwnedDevice, queue_family_index: u32, frames_in_flight: usize, worker_count: usize) -> Self {
+        assert!(worker_count > 0, "At least one command recording worker is required");
+
+        let workers = (0..worker_count)
+            .map(|worker| {
+                let pools = (0..frames_in_flight)
+                    .map(|frame| FramePool {
+                        pool: device.own(
+                            unsafe {
+                                device
+                                    .create_command_pool(
+                                        &vk::CommandPoolCreateInfo::default()
+                                            .queue_family_index(queue_family_index)
+                                            .flags(vk::CommandPoolCreateFlags::TRANSIENT),
+                                        None,
+                                    )
+                                    .expect("Failed to create command pool")
+                            },
+                            format!("command pool worker {} frame {}", worker, frame),
+                        ),
+                        primary: Vec::new(),
+                        secondary: Vec::new(),
+                        next_primary: 0,
+                        next_secondary: 0,
+                    })
+                    .collect::<Vec<_>>();
+                Arc::new(Mutex::new(pools))
+            })
+            .collect::<Vec<_>>();
+
+        let (recorders, recorder_threads) = (1..worker_count)
+            .map(|worker| {
+                let (sender, jobs) = mpsc::channel::<RecordingJob>();
+                let device = device.shared();
+                let pools = Arc::clone(&workers[worker]);
+                let thread = thread::Builder::new()
+                    .name(format!("command recorder {}", worker))
+                    .spawn(move || jobs.into_iter().for_each(|job| job.run(&device, &pools)))
+                    .expect("Failed to spawn command recording thread");
+                (sender, thread)
+            })
+            .unzip();
+
+        Self {
+            device: device.shared(),
+            workers,
+            recorders,
+            recorder_threads,
+            current_frame: 0,
+        }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    // Resets every worker's pool for the current slot of `sync`. The GPU must be done with that slot,
+    // i.e. call after VulkanSyncObjects::begin_frame.
+    pub fn begin_frame(&mut self, sync: &VulkanSyncObjects) {
+        sync.validate_slot_idle(&self.device, "command pools");
+
+        let frame = sync.current_frame();
+        self.current_frame = frame;
+
+        for worker in &self.workers {
+            let frame_pool = &mut worker.lock().unwrap()[frame];
+            unsafe {
+                self.device
+                    .reset_command_pool(frame_pool.pool.handle(), vk::CommandPoolResetFlags::empty())
+                    .expect("Failed to reset command pool");
+            }
+            frame_pool.next_primary = 0;
+            frame_pool.next_secondary = 0;
+        }
+    }
+
+    fn next_buffer(&self, worker: usize, level: vk::CommandBufferLevel) -> vk::CommandBuffer {
+        let mut pools = self.workers[worker].lock().unwrap();
+        pools[self.current_frame].next(&self.device, level)
+    }
+
+    // A primary buffer for the current frame, ready for one time submission.
+    pub fn begin_primary(&self, worker: usize) -> vk::CommandBuffer {
+        let command_buffer = self.next_buffer(worker, vk::CommandBufferLevel::PRIMARY);
+        unsafe {
+            self.device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .expect("Failed to begin primary command buffer");
+        }
+        command_buffer
+    }
+
+    // A secondary buffer continuing `inheritance`, executed from a render pass begun with
+    // SubpassContents::SECONDARY_COMMAND_BUFFERS.
+    pub fn begin_secondary(&self, worker: usize, inheritance: &SecondaryInheritance) -> vk::CommandBuffer {
+        let mut pools = self.workers[worker].lock().unwrap();
+        begin_secondary_buffer(&self.device, &mut pools[self.current_frame], inheritance)
+    }
+
+    // Records `job_count` secondary buffers in parallel, job i on worker i + 1, and returns them ended and in
+    // job order for cmd_execute_commands.
+    pub fn record_parallel<F>(&self, inheritance: &SecondaryInheritance, job_count: usize, record: F) -> Vec<vk::CommandBuffer>
+    where
+        F: Fn(usize, vk::CommandBuffer) + Send + Sync + 'static,
+    {
+        assert!(
+            job_count < self.worker_count(),
+            "record_parallel needs {} workers, the manager only has {}",
+            job_count + 1,
+            self.worker_count()
+        );
+
+        let record: SecondaryRecorder = Arc::new(record);
+        let (result_sender, results) = mpsc::channel();
+        let mut panicked = false;
+
+        for job in 0..job_count {
+            let recording_job = RecordingJob {
+                job,
+                frame: self.current_frame,
+                inheritance: *inheritance,
+                record: Arc::clone(&record),
+                results: result_sender.clone(),
+            };
+            if self.recorders[job].send(recording_job).is_err() {
+                panicked = true;
+            }
+        }
+        drop(result_sender);
+
+        let mut command_buffers = vec![vk::CommandBuffer::null(); job_count];
+        for (job, result) in results {
+            match result {
+                Ok(command_buffer) => command_buffers[job] = command_buffer,
+                Err(_) => panicked = true,
+            }
+        }
+
+        if panicked {
+            panic!("Command recording thread panicked");
+        }
+        command_buffers
+    }
+}
+
+// Stops the recording threads, the pools are destroyed with the fields right after.
+impl Drop for CommandPoolManager {
+    fn drop(&mut self) {
+        self.recorders.clear();
+        for thread in self.recorder_threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use ash::vk;
+
+
+// Renderer settings fixed at VulkanContext creation.
+#[derive(Clone, Debug)]
+pub struct RenderConfig {
+    pub frames_in_flight: usize,  // frames the CPU may record ahead of the GPU
+    pub recording_threads: usize,  // extra threads recording secondary command buffers, 0 records inline
+    pub depth_format: vk::Format,
+    pub near_plane: f32,  // meters
+    pub far_plane: f32,
+    pub reversed_z: bool,  // near maps to depth 1 and far to 0, much better float precision in the distance
+    pub msaa_samples: vk::SampleCountFlags,  // 1, 2, 4 or 8, clamped to the device limits. Above 1 no depth is submitted to XR
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            frames_in_flight: 2,
+            recording_threads: 0,
+            depth_format: vk::Format::D32_SFLOAT,
+            near_plane: 0.05,
+            far_plane: 100.0,
+            reversed_z: false,
+            msaa_samples: vk::SampleCountFlags::TYPE_1,  // keeps depth submission for reprojection
+        }
+    }
+}
+
+impl RenderConfig {
+    pub fn is_multisampled(&self) -> bool {
+        self.msaa_samples != vk::SampleCountFlags::TYPE_1
+    }
+
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.reversed_z { 0.0 } else { 1.0 }
+    }
+
+    pub fn depth_compare_op(&self) -> vk::CompareOp {
+        if self.reversed_z { vk::CompareOp::GREATER_OR_EQUAL } else { vk::CompareOp::LESS_OR_EQUAL }
+    }
+
+    // Distances (meters) at depth 0 and depth 1, as XrCompositionLayerDepthInfoKHR near_z / far_z expect them.
+    pub fn depth_range_meters(&self) -> (f32, f32) {
+        if self.reversed_z {
+            (self.far_plane, self.near_plane)
+        } else {
+            (self.near_plane, self.far_plane)
+        }
+    }
+
+    // Column major projection for one view from its OpenXR fov angles (radians), in Vulkan clip space
+    // (y down, depth 0..1). Near and far are swapped when reversed_z is set.
+    pub fn projection(&self, angle_left: f32, angle_right: f32, angle_up: f32, angle_down: f32) -> [f32; 16] {
+        let (tan_left, tan_right) = (angle_left.tan(), angle_right.tan());
+        let (tan_up, tan_down) = (angle_up.tan(), angle_down.tan());
+        let width = tan_right - tan_left;
+        let height = tan_down - tan_up;
+
+        let (near, far) = self.depth_range_meters();
+
+        [
+            2.0 / width, 0.0, 0.0, 0.0,
+            0.0, 2.0 / height, 0.0, 0.0,
+            (tan_right + tan_left) / width, (tan_up + tan_down) / height, -far / (far - near), -1.0,
+            0.0, 0.0, -(far * near) / (far - near), 0.0,
+        ]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Depth of a point `distance` meters in front of the viewer (-Z) after the perspective divide.
+    fn depth_at(projection: &[f32; 16], distance: f32) -> f32 {
+        let z = -distance;
+        let clip_z = projection[10] * z + projection[14];
+        let clip_w = projection[11] * z + projection[15];
+        clip_z / clip_w
+    }
+
+    #[test]
+    fn near_and_far_planes_map_to_the_depth_range_ends() {
+        for reversed_z in [false, true] {
+            let config = RenderConfig { reversed_z, ..RenderConfig::default() };
+            let projection = config.projection(-0.8, 0.7, 0.75, -0.85);
+
+            let near = depth_at(&projection, config.near_plane);
+            let far = depth_at(&projection, config.far_plane);
+            let middle = depth_at(&projection, 1.0);
+
+            let (expected_near, expected_far) = if reversed_z { (1.0, 0.0) } else { (0.0, 1.0) };
+            assert!((near - expected_near).abs() < 1e-5, "reversed_z {}: near at {}", reversed_z, near);
+            assert!((far - expected_far).abs() < 1e-5, "reversed_z {}: far at {}", reversed_z, far);
+            assert!(middle > 0.0 && middle < 1.0);
+            assert_eq!(config.depth_clear_value(), expected_far);
+        }
+    }
+}
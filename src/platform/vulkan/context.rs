@@ -7,441 +7,435 @@ use ash::vk::Handle;
 use std::ffi::CString;
 use std::marker::{PhantomData, PhantomPinned};
 use std::ptr;
+use std::sync::Arc;
 use openxr::{self as xr, Vulkan};
 
 use mlog::*;
 
 
 use crate::io;
-#[cfg(debug_assertions)]
-const USE_VK_VALIDATION_LAYERS: bool = true;
 
-#[cfg(not(debug_assertions))]
-const USE_VK_VALIDATION_LAYERS: bool = false;
-
-use super::shader;
+use super::command::{CommandPoolManager, SecondaryInheritance};
+use super::config::RenderConfig;
+use super::headless::OffscreenTarget;
+use super::memory::GpuAllocator;
+use super::pipeline::{GraphicsPipeline, GraphicsPipelineBuilder};
+use super::pipeline_cache::PipelineCache;
+use super::resource::*;
+use super::{instance, renderpass, shader};
 
 
 pub const VIEW_COUNT: u32 = 2;
 pub const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
 
-pub struct VulkanSyncObjects {
-    current_frame: usize,
-    image_available_semaphores: Vec<vk::Semaphore>,
-    render_finished_semaphores: Vec<vk::Semaphore>,
-    in_flight_fences: Vec<vk::Fence>,
-    images_in_flight: Vec<vk::Fence>,
-}
-
-impl Default for VulkanSyncObjects {
-    fn default() -> Self {
-        Self {
-            current_frame: 0,
-            image_available_semaphores: Vec::new(),
-            render_finished_semaphores: Vec::new(),
-            in_flight_fences: Vec::new(),
-            images_in_flight: Vec::new(),
-        }
-    }
-}
-
-
+// Fields are dropped in declaration order: GPU objects first, then the device (which reports leftovers), then the instance.
 pub struct VulkanContext {
-    pub entry: ash::Entry,
-    pub instance: ash::Instance,
-    pub physical_device: vk::PhysicalDevice,
-    pub device: ash::Device,
+    pub offscreen: Option<OffscreenTarget>,  // Render target used by the headless backend instead of an XrSwapchain
+    pub pipeline: GraphicsPipeline,
+    pub vert_shader_mod: OwnedShaderModule,
+    pub frag_shader_mod: OwnedShaderModule,
+    pub render_pass: OwnedRenderPass,
+    pub pipeline_cache: PipelineCache,  // saved to target/cache when dropped
     pub queue_family_index: u32,
     pub queue: vk::Queue,
     pub view_mask: u32,
-    pub render_pass: vk::RenderPass,
-    pub pipeline_layout: vk::PipelineLayout,
-    pub pipeline: vk::Pipeline,
-    pub vert_shader_mod: vk::ShaderModule,
-    pub frag_shader_mod: vk::ShaderModule,
     pub target_vk_version: u32,
-    pub swapchain: vk::SwapchainKHR,  // Swapchain handle
-    pub swapchain_images: Vec<vk::Image>,  // Swapchain images
-    pub framebuffers: Vec<vk::Framebuffer>,  // Framebuffers associated with the swapchain images
+    pub config: RenderConfig,
+    pub allocator: Arc<GpuAllocator>,  // dropped before the device, after everything allocated from it
+    pub physical_device: vk::PhysicalDevice,
+    pub device: OwnedDevice,
+    pub instance: OwnedInstance,
+    pub entry: ash::Entry,
 }
 
 
 
 impl VulkanContext {
+    // Lets the OpenXR runtime create the Vulkan instance and device (XR_KHR_vulkan_enable2) and pick the
+    // physical device it is connected to, adding the engine's own extensions and features on top.
+    // Vulkan failures and missing device capabilities are logged and reported as OpenXR graphics device errors.
     pub fn new(
         xr_instance: &xr::Instance,
         xr_system: xr::SystemId,
-    ) -> Self {
+        config: RenderConfig,
+    ) -> xr::Result<Self> {
         unsafe {
-            let entry = ash::Entry::load().unwrap();
+            let entry = ash::Entry::load().map_err(|e| {
+                crit!("Failed to load Vulkan loader: {}", e);
+                xr::sys::Result::ERROR_RUNTIME_FAILURE
+            })?;
             let target_vk_version = vk::make_api_version(0, 1, 1, 0);
 
-            // Vulkan instance, device, etc. initialization
-            let instance = ...;  // Vulkan instance setup
-            let physical_device = ...;  // Vulkan physical device setup
-            let device = ...;  // Vulkan logical device setup
-            let queue_family_index = ...;  // Queue family index
-            let queue = ...;  // Graphics queue
-            let render_pass = ...;  // Render pass setup
-            let swapchain = ...;  // Swapchain setup
-
-            // Get the swapchain images
-            let swapchain_images = device
-                .get_swapchain_images(swapchain)
-                .expect("Failed to get swapchain images");
-
-            // Create framebuffers for each swapchain image
-            let framebuffers = swapchain_images
-                .iter()
-                .map(|&image| {
-                    let image_view = device.create_image_view(
-                        &vk::ImageViewCreateInfo::default()
-                            .image(image)
-                            .view_type(vk::ImageViewType::TYPE_2D)
-                            .format(vk::Format::B8G8R8A8_SRGB)  // Ensure it matches swapchain format
-                            .subresource_range(vk::ImageSubresourceRange {
-                                aspect_mask: vk::ImageAspectFlags::COLOR,
-                                base_mip_level: 0,
-                                level_count: 1,
-                                base_array_layer: 0,
-                                layer_count: 1,
-                            }),
-                        None,
-                    ).expect("Failed to create image view");
-
-                    device.create_framebuffer(
-                        &vk::FramebufferCreateInfo::default()
-                            .render_pass(render_pass)
-                            .attachments(&[image_view])
-                            .width(1280)  // Swapchain image width
-                            .height(720)  // Swapchain image height
-                            .layers(1),
-                        None,
-                    ).expect("Failed to create framebuffer")
-                })
-                .collect::<Vec<_>>();
-
-            VulkanContext {
-                entry,
-                instance,
-                physical_device,
-                device,
+            let requirements = xr_instance.graphics_requirements::<xr::Vulkan>(xr_system)?;
+
+            let target = (
+                vk::api_version_major(target_vk_version) as u16,
+                vk::api_version_minor(target_vk_version) as u16,
+            );
+            let min = requirements.min_api_version_supported;
+            let max = requirements.max_api_version_supported;
+            if target < (min.major(), min.minor()) || target > (max.major(), max.minor()) {
+                return Err(device_unsupported(format!(
+                    "OpenXR runtime supports Vulkan {}.{} - {}.{}, engine targets {}.{}",
+                    min.major(), min.minor(), max.major(), max.minor(), target.0, target.1
+                )));
+            }
+
+            let app_name = CString::new("Neon").unwrap();
+            let engine_name = CString::new("Neon Engine").unwrap();
+            let app_info = vk::ApplicationInfo::default()
+                .application_name(&app_name)
+                .application_version(0)
+                .engine_name(&engine_name)
+                .engine_version(0)
+                .api_version(target_vk_version);
+
+            let layer_names = instance::validation_layer_names(&entry);
+
+            let instance = {
+                let raw_instance = xr_instance
+                    .create_vulkan_instance(
+                        xr_system,
+                        std::mem::transmute(entry.static_fn().get_instance_proc_addr),
+                        &vk::InstanceCreateInfo::default()
+                            .application_info(&app_info)
+                            .enabled_layer_names(&layer_names) as *const _ as *const _,
+                    )?
+                    .map_err(|e| vulkan_failure("create Vulkan instance", vk::Result::from_raw(e)))?;
+                OwnedInstance::new(ash::Instance::load(entry.static_fn(), vk::Instance::from_raw(raw_instance as _)))
+            };
+
+            // the runtime decides which GPU the HMD is connected to
+            let physical_device = vk::PhysicalDevice::from_raw(
+                xr_instance.vulkan_graphics_device(xr_system, instance.handle().as_raw() as _)? as _,
+            );
+
+            let properties = instance.get_physical_device_properties(physical_device);
+            let device_name = properties
+                .device_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            if properties.api_version < target_vk_version {
+                return Err(device_unsupported(format!(
+                    "Vulkan device {} doesn't support version {}.{}", device_name, target.0, target.1
+                )));
+            }
+            if !instance::supports_multiview(&instance, physical_device) {
+                return Err(device_unsupported(format!("Vulkan device {} doesn't support multiview", device_name)));
+            }
+            if !instance::supports_timeline_semaphore(&instance, physical_device, target_vk_version) {
+                return Err(device_unsupported(format!(
+                    "Vulkan device {} doesn't support timeline semaphores", device_name
+                )));
+            }
+
+            let queue_family_index = instance::find_graphics_queue_family(&instance, physical_device)
+                .ok_or_else(|| device_unsupported(format!("Vulkan device {} has no graphics queue", device_name)))?;
+
+            let mut config = config;
+            config.msaa_samples = instance::clamp_sample_count(&instance, physical_device, config.msaa_samples);
+
+            let device_extensions = instance::required_device_extensions(target_vk_version);
+            let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default().multiview(true);
+            let mut timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::default().timeline_semaphore(true);
+
+            // the runtime merges its own required extensions into this create info
+            let device = {
+                let raw_device = xr_instance
+                    .create_vulkan_device(
+                        xr_system,
+                        std::mem::transmute(entry.static_fn().get_instance_proc_addr),
+                        physical_device.as_raw() as _,
+                        &vk::DeviceCreateInfo::default()
+                            .queue_create_infos(&[vk::DeviceQueueCreateInfo::default()
+                                .queue_family_index(queue_family_index)
+                                .queue_priorities(&[1.0])])
+                            .enabled_extension_names(&device_extensions)
+                            .push_next(&mut multiview_features)
+                            .push_next(&mut timeline_features) as *const _ as *const _,
+                    )?
+                    .map_err(|e| vulkan_failure("create Vulkan device", vk::Result::from_raw(e)))?;
+                OwnedDevice::new(ash::Device::load(instance.fp_v1_0(), vk::Device::from_raw(raw_device as _)))
+            };
+
+            let queue = device.get_device_queue(queue_family_index, 0);
+            let allocator = GpuAllocator::new(&instance, &device, physical_device, target_vk_version)
+                .map_err(|e| vulkan_failure("create GPU memory allocator", e))?;
+
+            let view_mask = !(!0 << VIEW_COUNT);
+            let pipeline_cache = PipelineCache::load(&instance, &device, physical_device)
+                .map_err(|e| vulkan_failure("create pipeline cache", e))?;
+            let (render_pass, vert_shader_mod, frag_shader_mod, pipeline) =
+                Self::create_debug_resources(&device, &pipeline_cache, view_mask, &config)?;
+
+            success!("Vulkan context created through OpenXR on {}", device_name);
+
+            Ok(VulkanContext {
+                offscreen: None,
+                pipeline,
+                vert_shader_mod,
+                frag_shader_mod,
+                render_pass,
+                pipeline_cache,
                 queue_family_index,
                 queue,
-                view_mask: !(!0 << VIEW_COUNT),
-                render_pass,
-                pipeline_layout: vk::PipelineLayout::null(),
-                pipeline: vk::Pipeline::null(),
-                vert_shader_mod: vk::ShaderModule::null(),
-                frag_shader_mod: vk::ShaderModule::null(),
+                view_mask,
                 target_vk_version,
-                swapchain,
-                swapchain_images,
-                framebuffers,
-            }
+                config,
+                allocator,
+                physical_device,
+                device,
+                instance,
+                entry,
+            })
         }
     }
 
-    // Cleanup resources
-    pub fn cleanup(&self) {
-        unsafe {
-            for &framebuffer in &self.framebuffers {
-                self.device.destroy_framebuffer(framebuffer, None);
-            }
-            self.device.destroy_pipeline(self.pipeline, None);
-            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_shader_module(self.vert_shader_mod, None);
-            self.device.destroy_shader_module(self.frag_shader_mod, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            self.device.destroy_device(None);
-            self.instance.destroy_instance(None);
-        }
+
+    // Multiview render pass + the fullscreen debug pipeline, shared by the XR and headless constructors.
+    // Failures are logged and reported as ERROR_RUNTIME_FAILURE.
+    pub fn create_debug_resources(
+        device: &OwnedDevice,
+        pipeline_cache: &PipelineCache,
+        view_mask: u32,
+        config: &RenderConfig,
+    ) -> xr::Result<(OwnedRenderPass, OwnedShaderModule, OwnedShaderModule, GraphicsPipeline)> {
+        let render_pass = renderpass::create_multiview_render_pass(device, view_mask, config.depth_format, config.msaa_samples)
+            .map_err(|e| vulkan_failure("create multiview render pass", e))?;
+        let render_pass = device.own(render_pass, "multiview render pass");
+
+        io::shader_compiler::compile_all_shaders().map_err(|e| {
+            crit!("Failed to compile shaders: {}", e);
+            xr::sys::Result::ERROR_RUNTIME_FAILURE
+        })?;
+        let (vert_shader_mod, frag_shader_mod) = shader::create_shader_modules(device).map_err(|e| {
+            crit!("Failed to load debug shaders: {}", e);
+            xr::sys::Result::ERROR_RUNTIME_FAILURE
+        })?;
+
+        let pipeline = Self::create_debug_pipeline(
+            device,
+            pipeline_cache.handle(),
+            render_pass.handle(),
+            vert_shader_mod.handle(),
+            frag_shader_mod.handle(),
+            config,
+        )
+        .map_err(|e| vulkan_failure("create debug pipeline", e))?;
+
+        Ok((render_pass, vert_shader_mod, frag_shader_mod, pipeline))
     }
-    
-    pub fn create_command_buffers(&self) -> Vec<vk::CommandBuffer> {
-        let command_pool = vk::CommandPool::default();  // Placeholder: Initialize properly
-        let allocate_info = vk::CommandBufferAllocateInfo::default()
-            .command_pool(command_pool)
-            .level(vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(self.framebuffers.len() as u32);
-
-        let command_buffers = unsafe {
-            self.device.allocate_command_buffers(&allocate_info)
-                .expect("Failed to allocate command buffers")
+
+
+    // Recompiles out of date shaders (io::compile_changed_shaders, which also catches edited includes) and rebuilds
+    // the debug pipeline if one of its stages compiled. Must be called between frames. If anything fails (compile,
+    // loading the .spv, pipeline creation) the error is logged and the current modules and pipeline stay in use.
+    pub fn reload_shaders(&mut self) {
+        let report = match io::shader_compiler::compile_changed_shaders() {
+            Ok(report) => report,
+            Err(e) => {
+                crit!("Shader reload failed: {}", e);
+                return;
+            }
         };
+        for (source, diagnostics) in &report.failed {
+            crit!("Failed to compile shader {:?}, keeping the last good version:\n{}", source, diagnostics);
+        }
+
+        let rebuild = report.compiled.iter().any(|source| {
+            let file_name = source.file_name().and_then(|name| name.to_str());
+            matches!(file_name, Some(shader::DEBUG_VERT_SHADER | shader::DEBUG_FRAG_SHADER))
+        });
+        if !rebuild {
+            return;
+        }
 
-        for (i, &command_buffer) in command_buffers.iter().enumerate() {
-            let begin_info = vk::CommandBufferBeginInfo::default();
-
-            unsafe {
-                self.device.begin_command_buffer(command_buffer, &begin_info)
-                    .expect("Failed to begin command buffer");
-
-                let render_pass_info = vk::RenderPassBeginInfo::default()
-                    .render_pass(self.render_pass)
-                    .framebuffer(self.framebuffers[i])
-                    .render_area(vk::Rect2D {
-                        offset: vk::Offset2D { x: 0, y: 0 },
-                        extent: vk::Extent2D { width: 1280, height: 720 },
-                    })
-                    .clear_values(&[vk::ClearValue {
-                        color: vk::ClearColorValue {
-                            float32: [0.0, 0.0, 0.0, 1.0],  // Clear to black
-                        },
-                    }]);
-
-                self.device.cmd_begin_render_pass(command_buffer, &render_pass_info, vk::SubpassContents::INLINE);
-
-                // Record commands (bind pipeline, draw commands, etc.)
-                self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
-                self.device.cmd_draw(command_buffer, 3, 1, 0, 0);  // Example draw call for a triangle
-
-                self.device.cmd_end_render_pass(command_buffer);
-                self.device.end_command_buffer(command_buffer)
-                    .expect("Failed to record command buffer");
+        // the stage that didn't change reloads its last good .spv, which is what it is already running
+        let (vert_shader_mod, frag_shader_mod) = match shader::create_shader_modules(&self.device) {
+            Ok(modules) => modules,
+            Err(e) => {
+                crit!("Shader reload failed, keeping the last good pipeline: {}", e);
+                return;
             }
+        };
+        let pipeline = match Self::create_debug_pipeline(
+            &self.device,
+            self.pipeline_cache.handle(),
+            self.render_pass.handle(),
+            vert_shader_mod.handle(),
+            frag_shader_mod.handle(),
+            &self.config,
+        ) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                crit!("Failed to rebuild debug pipeline, keeping the last good one: {}", e);
+                return;
+            }
+        };
+
+        // frames still in flight may be using the old pipeline
+        unsafe {
+            let _ = self.device.device_wait_idle();
         }
+        self.pipeline = pipeline;
+        self.vert_shader_mod = vert_shader_mod;
+        self.frag_shader_mod = frag_shader_mod;
+        success!("Rebuilt debug pipeline");
+    }
 
-        command_buffers
+
+    // Submits a recorded frame, `fence` is signaled once the GPU has finished with it.
+    pub fn render_frame(&self, command_buffer: vk::CommandBuffer, fence: vk::Fence) {
+        unsafe {
+            self.device
+                .queue_submit(
+                    self.queue,
+                    &[vk::SubmitInfo::default().command_buffers(&[command_buffer])],
+                    fence,
+                )
+                .expect("Failed to submit frame");
+        }
     }
 
 
-    pub fn render_frame(&self, command_buffer: vk::CommandBuffer) {
-        // Logic to submit the command buffer and present the rendered image using the swapchain
+    // Records the multiview pass into `framebuffer`, all VIEW_COUNT layers are drawn by a single draw call.
+    pub fn record_multiview_pass(&self, command_buffer: vk::CommandBuffer, framebuffer: vk::Framebuffer, extent: vk::Extent2D) {
+        self.begin_multiview_pass(command_buffer, framebuffer, extent, vk::SubpassContents::INLINE);
+        record_debug_draw(&self.device, self.pipeline.handle(), command_buffer, extent);
+        unsafe { self.device.cmd_end_render_pass(command_buffer) };
     }
 
 
-}
+    // Same pass, but the draws are recorded into secondary command buffers on the manager's workers
+    // (one per `job_count`) and executed from `command_buffer`.
+    pub fn record_multiview_pass_parallel(
+        &self,
+        commands: &CommandPoolManager,
+        command_buffer: vk::CommandBuffer,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        job_count: usize,
+    ) {
+        let inheritance = SecondaryInheritance {
+            render_pass: self.render_pass.handle(),
+            subpass: 0,
+            framebuffer,
+        };
+
+        // only the first job draws for now, the others are empty until there is a scene to split up
+        let device = self.device.shared();
+        let pipeline = self.pipeline.handle();
+        let secondaries = commands.record_parallel(&inheritance, job_count, move |job, secondary| {
+            if job == 0 {
+                record_debug_draw(&device, pipeline, secondary, extent);
+            }
+        });
 
+        self.begin_multiview_pass(command_buffer, framebuffer, extent, vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+        unsafe {
+            if !secondaries.is_empty() {
+                self.device.cmd_execute_commands(command_buffer, &secondaries);
+            }
+            self.device.cmd_end_render_pass(command_buffer);
+        }
+    }
 
 
+    fn begin_multiview_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        contents: vk::SubpassContents,
+    ) {
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],  // Clear to black
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: self.config.depth_clear_value(),
+                    stencil: 0,
+                },
+            },
+        ];
+
+        let render_pass_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass.handle())
+            .framebuffer(framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .clear_values(&clear_values);
+
+        unsafe { self.device.cmd_begin_render_pass(command_buffer, &render_pass_info, contents) };
+    }
 
 
+    // Builds the fullscreen debug pipeline used by both the XR and headless backends.
+    pub fn create_debug_pipeline(
+        device: &OwnedDevice,
+        pipeline_cache: vk::PipelineCache,
+        render_pass: vk::RenderPass,
+        vert_shader: vk::ShaderModule,
+        frag_shader: vk::ShaderModule,
+        config: &RenderConfig,
+    ) -> Result<GraphicsPipeline, vk::Result> {
+        GraphicsPipelineBuilder::new(render_pass, config)
+            .pipeline_cache(pipeline_cache)
+            .shader(vk::ShaderStageFlags::VERTEX, vert_shader)
+            .shader(vk::ShaderStageFlags::FRAGMENT, frag_shader)
+            .color_write_mask(vk::ColorComponentFlags::R | vk::ColorComponentFlags::G | vk::ColorComponentFlags::B)
+            .build(device, "debug pipeline")
+    }
 
+}
 
 
+// Nothing may be destroyed while the GPU still uses it, the fields are dropped right after this.
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+        }
+    }
+}
+
 
+// OpenXR error codes can't carry the details, so they are logged where the failure happens.
+// Dynamic state is not inherited by secondary command buffers, so viewport / scissor are set with the draw.
+// Takes plain handles so recording threads can call it without borrowing the context.
+fn record_debug_draw(device: &ash::Device, pipeline: vk::Pipeline, command_buffer: vk::CommandBuffer, extent: vk::Extent2D) {
+    unsafe {
+        device.cmd_set_viewport(command_buffer, 0, &[vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }]);
+        device.cmd_set_scissor(command_buffer, 0, &[vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        }]);
+
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        device.cmd_draw(command_buffer, 3, 1, 0, 0);  // fullscreen triangle
+    }
+}
 
+fn device_unsupported(reason: String) -> xr::sys::Result {
+    crit!("{}", reason);
+    xr::sys::Result::ERROR_GRAPHICS_DEVICE_INVALID
+}
 
-// old ver.
-
-// impl VulkanContext { 
-//     fn new(xr_instance : xr::Instance, xr_system : xr::SystemId) {
-
-//         unsafe {
-//             let vk_entry = ash::Entry::load().unwrap();
-//             let target_vk_version = vk::make_api_version(0, 1, 1, 0);
-
-
-
-//             let vk_app_info = vk::ApplicationInfo::default()
-//                 .application_version(0)
-//                 .engine_version(0)
-//                 .api_version(target_vk_version);
-
-//                 let vk_instance = {
-//                     let vk_instance = xr_instance
-//                         .create_vulkan_instance(
-//                             xr_system,
-//                             std::mem::transmute(vk_entry.static_fn().get_instance_proc_addr),
-//                             &vk::InstanceCreateInfo::default().application_info(&vk_app_info) as *const _
-//                                 as *const _,
-//                         )
-//                         .expect("XR error creating Vulkan instance")
-//                         .map_err(vk::Result::from_raw)
-//                         .expect("Vulkan error creating Vulkan instance");
-//                     ash::Instance::load(
-//                         vk_entry.static_fn(),
-//                         vk::Instance::from_raw(vk_instance as _),
-//                     )
-//                 };
-
-
-//             let vk_physical_device = vk::PhysicalDevice::from_raw(
-//                 xr_instance
-//                     .vulkan_graphics_device(xr_system, vk_instance.handle().as_raw() as _)
-//                     .unwrap() as _,
-//             );
-
-//             let vk_device_properties = vk_instance.get_physical_device_properties(vk_physical_device);
-//             if vk_device_properties.api_version < target_vk_version {
-//                 vk_instance.destroy_instance(None);
-//                 panic!("Vulkan phyiscal device doesn't support version 1.1");
-//             }
-    
-//             let vk_queue_family_index = vk_instance
-//             .get_physical_device_queue_family_properties(vk_physical_device)
-//             .into_iter()
-//             .enumerate()
-//             .find_map(|(queue_family_index, info)| {
-//                 if info.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-//                     Some(queue_family_index as u32)
-//                 } else {
-//                     None
-//                 }
-//             })
-//             .expect("Vulkan device has no graphics queue");
-
-            
-//             let vk_device = {
-//                 let vk_device = xr_instance
-//                     .create_vulkan_device(
-//                         xr_system,
-//                         std::mem::transmute(vk_entry.static_fn().get_instance_proc_addr),
-//                         vk_physical_device.as_raw() as _,
-//                         &vk::DeviceCreateInfo::default()
-//                             .queue_create_infos(&[vk::DeviceQueueCreateInfo::default()
-//                                 .queue_family_index(vk_queue_family_index)
-//                                 .queue_priorities(&[1.0])])
-//                             .push_next(&mut vk::PhysicalDeviceMultiviewFeatures {
-//                                 multiview: vk::TRUE,
-//                                 ..Default::default()
-//                             }) as *const _ as *const _,
-//                     )
-//                     .expect("XR error creating Vulkan device")
-//                     .map_err(vk::Result::from_raw)
-//                     .expect("Vulkan error creating Vulkan device");
-    
-//                 ash::Device::load(vk_instance.fp_v1_0(), vk::Device::from_raw(vk_device as _))
-//             };
-            
-//             let vk_queue = vk_device.get_device_queue(vk_queue_family_index, 0);
-    
-//             let vk_view_mask = !(!0 << VIEW_COUNT);
-
-//             let vk_render_pass = vk_device
-//                 .create_render_pass(
-//                     &vk::RenderPassCreateInfo::default()
-//                         .attachments(&[vk::AttachmentDescription {
-//                             format: COLOR_FORMAT,
-//                             samples: vk::SampleCountFlags::TYPE_1,
-//                             load_op: vk::AttachmentLoadOp::CLEAR,
-//                             store_op: vk::AttachmentStoreOp::STORE,
-//                             initial_layout: vk::ImageLayout::UNDEFINED,
-//                             final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-//                             ..Default::default()
-//                         }])
-//                         .subpasses(&[vk::SubpassDescription::default()
-//                             .color_attachments(&[vk::AttachmentReference {
-//                                 attachment: 0,
-//                                 layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-//                             }])
-//                             .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)])
-//                         .dependencies(&[vk::SubpassDependency {
-//                             src_subpass: vk::SUBPASS_EXTERNAL,
-//                             dst_subpass: 0,
-//                             src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-//                             dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-//                             dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-//                             ..Default::default()
-//                         }])
-//                         .push_next(
-//                             &mut vk::RenderPassMultiviewCreateInfo::default()
-//                                 .view_masks(&[vk_view_mask])
-//                                 .correlation_masks(&[vk_view_mask]),
-//                         ),
-//                     None,
-//                 )
-//                 .unwrap();
-
-
-//                 // load and compile shaders:
-//                 io::shader_compiler::compile_all_shaders().expect("Something went wrong with shader compilation");
-//                 let (vert_shader, frag_shader) = shader::create_shader_modules(&vk_device);
-                
-//                 let pipeline_layout = vk_device
-//                 .create_pipeline_layout(
-//                     &vk::PipelineLayoutCreateInfo::default().set_layouts(&[]),
-//                     None,
-//                 )
-//                 .unwrap();
-
-//                 let noop_stencil_state = vk::StencilOpState {
-//                     fail_op: vk::StencilOp::KEEP,
-//                     pass_op: vk::StencilOp::KEEP,
-//                     depth_fail_op: vk::StencilOp::KEEP,
-//                     compare_op: vk::CompareOp::ALWAYS,
-//                     compare_mask: 0,
-//                     write_mask: 0,
-//                     reference: 0,
-//                 };
-                
-//                 let pipeline = vk_device
-//                 .create_graphics_pipelines(
-//                     vk::PipelineCache::null(),
-//                     &[vk::GraphicsPipelineCreateInfo::default()
-//                         .stages(&[
-//                             vk::PipelineShaderStageCreateInfo {
-//                                 stage: vk::ShaderStageFlags::VERTEX,
-//                                 module: vert_shader,
-//                                 p_name: b"main\0".as_ptr() as _,
-//                                 ..Default::default()
-//                             },
-//                             vk::PipelineShaderStageCreateInfo {
-//                                 stage: vk::ShaderStageFlags::FRAGMENT,
-//                                 module: frag_shader,
-//                                 p_name: b"main\0".as_ptr() as _,
-//                                 ..Default::default()
-//                             },
-//                         ])
-//                         .vertex_input_state(&vk::PipelineVertexInputStateCreateInfo::default())
-//                         .input_assembly_state(
-//                             &vk::PipelineInputAssemblyStateCreateInfo::default()
-//                                 .topology(vk::PrimitiveTopology::TRIANGLE_LIST),
-//                         )
-//                         .viewport_state(
-//                             &vk::PipelineViewportStateCreateInfo::default()
-//                                 .scissor_count(1)
-//                                 .viewport_count(1),
-//                         )
-//                         .rasterization_state(
-//                             &vk::PipelineRasterizationStateCreateInfo::default()
-//                                 .cull_mode(vk::CullModeFlags::NONE)
-//                                 .polygon_mode(vk::PolygonMode::FILL)
-//                                 .line_width(1.0),
-//                         )
-//                         .multisample_state(
-//                             &vk::PipelineMultisampleStateCreateInfo::default()
-//                                 .rasterization_samples(vk::SampleCountFlags::TYPE_1),
-//                         )
-//                         .depth_stencil_state(
-//                             &vk::PipelineDepthStencilStateCreateInfo::default()
-//                                 .depth_test_enable(false)
-//                                 .depth_write_enable(false)
-//                                 .front(noop_stencil_state)
-//                                 .back(noop_stencil_state),
-//                         )
-//                         .color_blend_state(
-//                             &vk::PipelineColorBlendStateCreateInfo::default().attachments(&[
-//                                 vk::PipelineColorBlendAttachmentState {
-//                                     blend_enable: vk::TRUE,
-//                                     src_color_blend_factor: vk::BlendFactor::ONE,
-//                                     dst_color_blend_factor: vk::BlendFactor::ZERO,
-//                                     color_blend_op: vk::BlendOp::ADD,
-//                                     color_write_mask: vk::ColorComponentFlags::R
-//                                         | vk::ColorComponentFlags::G
-//                                         | vk::ColorComponentFlags::B,
-//                                     ..Default::default()
-//                                 },
-//                             ]),
-//                         )
-//                         .dynamic_state(
-//                             &vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&[
-//                                 vk::DynamicState::VIEWPORT,
-//                                 vk::DynamicState::SCISSOR,
-//                             ]),
-//                         )
-//                         .layout(pipeline_layout)
-//                         .render_pass(vk_render_pass)
-//                         .subpass(0)],
-//                     None,
-//                 )
-//                 .unwrap()[0];
-
-//                 vk_device.destroy_shader_module(vert_shader, None);
-//                 vk_device.destroy_shader_module(frag_shader, None);
-
-
-//         }
-//     }
-// }
\ No newline at end of file
+// Also used by the XR swapchain, whose setup reports errors as OpenXR results too.
+pub fn vulkan_failure(action: &str, result: vk::Result) -> xr::sys::Result {
+    crit!("Failed to {}: {}", action, result);
+    xr::sys::Result::ERROR_RUNTIME_FAILURE
+}
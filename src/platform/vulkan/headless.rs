@@ -0,0 +1,353 @@
+use ash::vk;
+
+use std::sync::Arc;
+
+use mlog::*;
+
+use super::config::RenderConfig;
+use super::context::{VulkanContext, COLOR_FORMAT, VIEW_COUNT};
+use super::memory::{AllocatedBuffer, AllocatedImage, GpuAllocator};
+use super::pipeline_cache::PipelineCache;
+use super::resource::*;
+use super::{instance, renderpass, utils};
+
+
+// Offscreen stand-in for the XR swapchain: a single VIEW_COUNT layered color image rendered through
+// the multiview render pass, plus a host visible buffer the layers are copied into after every frame.
+// Fields drop in declaration order, the framebuffer and views before their images.
+pub struct OffscreenTarget {
+    pub extent: vk::Extent2D,
+    fence: OwnedFence,
+    command_buffer: vk::CommandBuffer,  // freed with command_pool
+    command_pool: OwnedCommandPool,
+    readback_buffer: AllocatedBuffer,
+    pub framebuffer: OwnedFramebuffer,
+    pub image_view: OwnedImageView,
+    pub depth_view: OwnedImageView,
+    msaa_view: Option<OwnedImageView>,
+    pub image: AllocatedImage,  // single sample, the multisample target is resolved into it
+    pub depth_image: AllocatedImage,
+    msaa_image: Option<AllocatedImage>,
+}
+
+impl OffscreenTarget {
+    pub fn new(
+        device: &OwnedDevice,
+        allocator: &Arc<GpuAllocator>,
+        queue_family_index: u32,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        config: &RenderConfig,
+    ) -> Self {
+        unsafe {
+            let image = allocator.create_render_target(
+                COLOR_FORMAT,
+                extent,
+                VIEW_COUNT,
+                vk::SampleCountFlags::TYPE_1,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+                "offscreen image",
+            );
+
+            let image_view = device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo::default()
+                        .image(image.image)
+                        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                        .format(COLOR_FORMAT)
+                        .subresource_range(utils::color_subresource_range(VIEW_COUNT)),
+                    None,
+                )
+                .expect("Failed to create offscreen image view");
+            let image_view = device.own(image_view, "offscreen image view");
+
+            let depth_image = if config.is_multisampled() {
+                allocator.create_transient_attachment(
+                    config.depth_format,
+                    extent,
+                    VIEW_COUNT,
+                    config.msaa_samples,
+                    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                    "offscreen msaa depth image",
+                )
+            } else {
+                allocator.create_render_target(
+                    config.depth_format,
+                    extent,
+                    VIEW_COUNT,
+                    vk::SampleCountFlags::TYPE_1,
+                    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                    "offscreen depth image",
+                )
+            };
+            let depth_view = device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo::default()
+                        .image(depth_image.image)
+                        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                        .format(config.depth_format)
+                        .subresource_range(utils::depth_subresource_range(VIEW_COUNT)),
+                    None,
+                )
+                .expect("Failed to create offscreen depth view");
+            let depth_view = device.own(depth_view, "offscreen depth view");
+
+            let msaa_image = config.is_multisampled().then(|| {
+                allocator.create_transient_attachment(
+                    COLOR_FORMAT,
+                    extent,
+                    VIEW_COUNT,
+                    config.msaa_samples,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                    "offscreen msaa image",
+                )
+            });
+            let msaa_view = msaa_image.as_ref().map(|msaa_image| {
+                let msaa_view = device
+                    .create_image_view(
+                        &vk::ImageViewCreateInfo::default()
+                            .image(msaa_image.image)
+                            .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+                            .format(COLOR_FORMAT)
+                            .subresource_range(utils::color_subresource_range(VIEW_COUNT)),
+                        None,
+                    )
+                    .expect("Failed to create offscreen msaa view");
+                device.own(msaa_view, "offscreen msaa view")
+            });
+
+            // multiview framebuffers always have a single layer, the views are selected by the render pass view mask
+            let framebuffer = device
+                .create_framebuffer(
+                    &vk::FramebufferCreateInfo::default()
+                        .render_pass(render_pass)
+                        .attachments(&renderpass::multiview_attachments(
+                            image_view.handle(),
+                            depth_view.handle(),
+                            msaa_view.as_ref().map(|msaa_view| msaa_view.handle()),
+                        ))
+                        .width(extent.width)
+                        .height(extent.height)
+                        .layers(1),
+                    None,
+                )
+                .expect("Failed to create offscreen framebuffer");
+            let framebuffer = device.own(framebuffer, "offscreen framebuffer");
+
+            let readback_size = Self::view_size_bytes_for(extent) as u64 * VIEW_COUNT as u64;
+            let readback_buffer = allocator.create_readback_buffer(readback_size, "readback buffer");
+
+            let command_pool = device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo::default()
+                        .queue_family_index(queue_family_index)
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                    None,
+                )
+                .expect("Failed to create offscreen command pool");
+            let command_pool = device.own(command_pool, "offscreen command pool");
+
+            let command_buffer = device
+                .allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::default()
+                        .command_pool(command_pool.handle())
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+                .expect("Failed to allocate offscreen command buffer")[0];
+
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .expect("Failed to create offscreen fence");
+            let fence = device.own(fence, "offscreen fence");
+
+            OffscreenTarget {
+                extent,
+                fence,
+                command_buffer,
+                command_pool,
+                readback_buffer,
+                framebuffer,
+                image_view,
+                depth_view,
+                msaa_view,
+                image,
+                depth_image,
+                msaa_image,
+            }
+        }
+    }
+
+    fn view_size_bytes_for(extent: vk::Extent2D) -> usize {
+        extent.width as usize * extent.height as usize * 4  // COLOR_FORMAT is 4 bytes per texel
+    }
+
+    pub fn view_size_bytes(&self) -> usize {
+        Self::view_size_bytes_for(self.extent)
+    }
+}
+
+
+impl VulkanContext {
+    // Builds a context without OpenXR: own Vulkan instance + device, rendering into an OffscreenTarget.
+    // Works on software ICDs such as lavapipe, set NEON_VK_DEVICE=llvmpipe to force it.
+    pub fn new_headless(extent: vk::Extent2D, config: RenderConfig) -> Self {
+        unsafe {
+            let entry = ash::Entry::load().expect("Failed to load Vulkan loader");
+            let target_vk_version = vk::make_api_version(0, 1, 1, 0);
+
+            let instance = OwnedInstance::new(instance::create_headless_instance(&entry, target_vk_version));
+
+            let (physical_device, queue_family_index) =
+                instance::select_headless_physical_device(&instance, target_vk_version)
+                    .expect("No Vulkan device supports the headless multiview pipeline");
+
+            let mut config = config;
+            config.msaa_samples = instance::clamp_sample_count(&instance, physical_device, config.msaa_samples);
+
+            let device = instance
+                .create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::default()
+                        .queue_create_infos(&[vk::DeviceQueueCreateInfo::default()
+                            .queue_family_index(queue_family_index)
+                            .queue_priorities(&[1.0])])
+                        .push_next(&mut vk::PhysicalDeviceMultiviewFeatures {
+                            multiview: vk::TRUE,
+                            ..Default::default()
+                        }),
+                    None,
+                )
+                .expect("Failed to create headless Vulkan device");
+            let device = OwnedDevice::new(device);
+            let allocator = GpuAllocator::new(&instance, &device, physical_device, target_vk_version)
+                .expect("Failed to create GPU memory allocator");
+
+            let queue = device.get_device_queue(queue_family_index, 0);
+
+            let view_mask = !(!0 << VIEW_COUNT);
+            let pipeline_cache =
+                PipelineCache::load(&instance, &device, physical_device).expect("Failed to create pipeline cache");
+            let (render_pass, vert_shader_mod, frag_shader_mod, pipeline) =
+                Self::create_debug_resources(&device, &pipeline_cache, view_mask, &config)
+                    .expect("Failed to create debug resources");
+
+            let offscreen = OffscreenTarget::new(
+                &device,
+                &allocator,
+                queue_family_index,
+                render_pass.handle(),
+                extent,
+                &config,
+            );
+
+            success!("Headless Vulkan context created ({}x{} x {} views)", extent.width, extent.height, VIEW_COUNT);
+
+            VulkanContext {
+                offscreen: Some(offscreen),
+                pipeline,
+                vert_shader_mod,
+                frag_shader_mod,
+                render_pass,
+                pipeline_cache,
+                queue_family_index,
+                queue,
+                view_mask,
+                target_vk_version,
+                config,
+                allocator,
+                physical_device,
+                device,
+                instance,
+                entry,
+            }
+        }
+    }
+
+    // Renders all views into the offscreen target and copies them into the readback buffer.
+    // Blocks until the GPU is done, so `read_offscreen_view` can be called right after.
+    pub fn render_offscreen(&self) {
+        let target = self.offscreen.as_ref().expect("render_offscreen called on a context without an offscreen target");
+        let command_buffer = target.command_buffer;
+
+        unsafe {
+            self.device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .expect("Failed to begin offscreen command buffer");
+
+            self.record_multiview_pass(command_buffer, target.framebuffer.handle(), target.extent);
+
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(target.image.image)
+                    .subresource_range(utils::color_subresource_range(VIEW_COUNT))],
+            );
+
+            let regions = (0..VIEW_COUNT)
+                .map(|view| {
+                    vk::BufferImageCopy::default()
+                        .buffer_offset(view as u64 * target.view_size_bytes() as u64)
+                        .image_subresource(vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: view,
+                            layer_count: 1,
+                        })
+                        .image_extent(vk::Extent3D { width: target.extent.width, height: target.extent.height, depth: 1 })
+                })
+                .collect::<Vec<_>>();
+
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                target.image.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                target.readback_buffer.buffer,
+                &regions,
+            );
+
+            self.device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to record offscreen command buffer");
+
+            self.device
+                .queue_submit(
+                    self.queue,
+                    &[vk::SubmitInfo::default().command_buffers(&[command_buffer])],
+                    target.fence.handle(),
+                )
+                .expect("Failed to submit offscreen frame");
+
+            self.device
+                .wait_for_fences(&[target.fence.handle()], true, u64::MAX)
+                .expect("Failed to wait for offscreen frame");
+            self.device
+                .reset_fences(&[target.fence.handle()])
+                .expect("Failed to reset offscreen fence");
+        }
+    }
+
+    // Returns the tightly packed RGBA8 texels of one view from the last `render_offscreen` call.
+    pub fn read_offscreen_view(&self, view: u32) -> Vec<u8> {
+        assert!(view < VIEW_COUNT, "view index {} out of range", view);
+        let target = self.offscreen.as_ref().expect("read_offscreen_view called on a context without an offscreen target");
+        let size = target.view_size_bytes();
+
+        // the readback buffer is persistently mapped and coherent
+        target.readback_buffer.read(view as u64 * size as u64, size).to_vec()
+    }
+}
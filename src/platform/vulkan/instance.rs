@@ -0,0 +1,209 @@
+use ash::vk;
+use std::ffi::{c_char, CStr, CString};
+
+use mlog::*;
+
+#[cfg(debug_assertions)]
+pub const USE_VK_VALIDATION_LAYERS: bool = true;
+
+#[cfg(not(debug_assertions))]
+pub const USE_VK_VALIDATION_LAYERS: bool = false;
+
+const VALIDATION_LAYER_NAME: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+// Environment variable used to force a specific physical device by (partial) name, e.g. "llvmpipe" for lavapipe.
+pub const DEVICE_OVERRIDE_ENV: &str = "NEON_VK_DEVICE";
+
+
+// Creates a standalone Vulkan instance, used when no OpenXR runtime is available to create one for us.
+pub fn create_headless_instance(entry: &ash::Entry, target_vk_version: u32) -> ash::Instance {
+    let app_name = CString::new("Neon").unwrap();
+    let engine_name = CString::new("Neon Engine").unwrap();
+
+    let app_info = vk::ApplicationInfo::default()
+        .application_name(&app_name)
+        .application_version(0)
+        .engine_name(&engine_name)
+        .engine_version(0)
+        .api_version(target_vk_version);
+
+    let layer_names = validation_layer_names(entry);
+
+    let create_info = vk::InstanceCreateInfo::default()
+        .application_info(&app_info)
+        .enabled_layer_names(&layer_names);
+
+    unsafe {
+        entry
+            .create_instance(&create_info, None)
+            .expect("Failed to create headless Vulkan instance")
+    }
+}
+
+
+// Validation layer to enable when USE_VK_VALIDATION_LAYERS is set and the layer is installed.
+pub fn validation_layer_names(entry: &ash::Entry) -> Vec<*const c_char> {
+    let mut layer_names = Vec::new();
+    if USE_VK_VALIDATION_LAYERS {
+        let available_layers = unsafe {
+            entry
+                .enumerate_instance_layer_properties()
+                .unwrap_or_default()
+        };
+
+        let has_validation = available_layers.iter().any(|layer| {
+            layer.layer_name_as_c_str().map_or(false, |name| name == VALIDATION_LAYER_NAME)
+        });
+
+        if has_validation {
+            layer_names.push(VALIDATION_LAYER_NAME.as_ptr());
+        } else {
+            info!("Vulkan validation layers requested but not installed, continuing without them");
+        }
+    }
+    layer_names
+}
+
+
+// Picks a physical device that can run the multiview pipeline without any surface / XR requirements.
+// Software ICDs (lavapipe, swiftshader) are accepted but only chosen when nothing better is present,
+// unless explicitly requested through NEON_VK_DEVICE.
+pub fn select_headless_physical_device(
+    instance: &ash::Instance,
+    target_vk_version: u32,
+) -> Option<(vk::PhysicalDevice, u32)> {
+    let devices = unsafe {
+        instance
+            .enumerate_physical_devices()
+            .expect("Failed to enumerate physical devices")
+    };
+
+    let device_override = std::env::var(DEVICE_OVERRIDE_ENV).ok().map(|name| name.to_lowercase());
+
+    devices
+        .into_iter()
+        .filter_map(|physical_device| {
+            let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+            let device_name = properties
+                .device_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            if let Some(wanted) = &device_override {
+                if !device_name.to_lowercase().contains(wanted.as_str()) {
+                    return None;
+                }
+            }
+
+            if properties.api_version < target_vk_version {
+                info!("    Skipping {}: Vulkan version too old", device_name);
+                return None;
+            }
+
+            if !supports_multiview(instance, physical_device) {
+                info!("    Skipping {}: multiview not supported", device_name);
+                return None;
+            }
+
+            let queue_family_index = find_graphics_queue_family(instance, physical_device)?;
+
+            let score = match properties.device_type {
+                vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+                vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+                vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+                vk::PhysicalDeviceType::CPU => 1,
+                _ => 0,
+            };
+
+            Some((score, physical_device, queue_family_index, device_name))
+        })
+        .max_by_key(|(score, ..)| *score)
+        .map(|(_, physical_device, queue_family_index, device_name)| {
+            info!("Selected Vulkan device: {}", device_name);
+            (physical_device, queue_family_index)
+        })
+}
+
+
+pub fn supports_multiview(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> bool {
+    let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut multiview_features);
+
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+    multiview_features.multiview == vk::TRUE
+}
+
+
+pub fn find_graphics_queue_family(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> Option<u32> {
+    unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+        .into_iter()
+        .enumerate()
+        .find_map(|(queue_family_index, info)| {
+            if info.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
+                Some(queue_family_index as u32)
+            } else {
+                None
+            }
+        })
+}
+
+
+// Timeline semaphores are core in 1.2, on 1.1 they need VK_KHR_timeline_semaphore.
+pub fn supports_timeline_semaphore(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    target_vk_version: u32,
+) -> bool {
+    if target_vk_version < vk::API_VERSION_1_2
+        && !supports_device_extension(instance, physical_device, ash::khr::timeline_semaphore::NAME)
+    {
+        return false;
+    }
+
+    let mut timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut timeline_features);
+
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+    timeline_features.timeline_semaphore == vk::TRUE
+}
+
+
+pub fn supports_device_extension(instance: &ash::Instance, physical_device: vk::PhysicalDevice, name: &CStr) -> bool {
+    unsafe { instance.enumerate_device_extension_properties(physical_device) }
+        .unwrap_or_default()
+        .iter()
+        .any(|extension| extension.extension_name_as_c_str().map_or(false, |extension_name| extension_name == name))
+}
+
+
+// Device extensions the engine needs on top of whatever the OpenXR runtime adds itself.
+pub fn required_device_extensions(target_vk_version: u32) -> Vec<*const c_char> {
+    let mut extensions = Vec::new();
+    if target_vk_version < vk::API_VERSION_1_2 {
+        extensions.push(ash::khr::timeline_semaphore::NAME.as_ptr());
+    }
+    extensions
+}
+
+
+// Highest sample count up to `requested` usable for both color and depth attachments.
+pub fn clamp_sample_count(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    requested: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+    let supported = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+    let samples = [vk::SampleCountFlags::TYPE_8, vk::SampleCountFlags::TYPE_4, vk::SampleCountFlags::TYPE_2]
+        .into_iter()
+        .find(|&samples| samples.as_raw() <= requested.as_raw() && supported.contains(samples))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1);
+
+    if samples != requested {
+        info!("{:?} MSAA not supported, using {:?}", requested, samples);
+    }
+    samples
+}
@@ -0,0 +1,409 @@
+use ash::vk;
+use ash::vk::Handle;
+use vk_mem::Alloc;
+
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+
+use mlog::*;
+
+use super::resource::{OwnedDevice, TrackedDevice};
+
+
+// vk-mem allocator shared by every allocated image / buffer, which keep it alive until they are dropped.
+pub struct GpuAllocator {
+    allocator: ManuallyDrop<vk_mem::Allocator>,
+    device: Arc<TrackedDevice>,
+    memory_heaps: Vec<vk::MemoryHeap>,
+}
+
+impl GpuAllocator {
+    pub fn new(
+        instance: &ash::Instance,
+        device: &OwnedDevice,
+        physical_device: vk::PhysicalDevice,
+        target_vk_version: u32,
+    ) -> Result<Arc<Self>, vk::Result> {
+        let mut create_info = vk_mem::AllocatorCreateInfo::new(instance, device, physical_device);
+        create_info.vulkan_api_version = target_vk_version;
+
+        let allocator = unsafe { vk_mem::Allocator::new(create_info) }?;
+
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let memory_heaps = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize].to_vec();
+
+        Ok(Arc::new(Self {
+            allocator: ManuallyDrop::new(allocator),
+            device: device.shared(),
+            memory_heaps,
+        }))
+    }
+
+    // Device local image for attachments that are never touched by the host (depth, MSAA, render targets).
+    pub fn create_render_target(
+        self: &Arc<Self>,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        layers: u32,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
+        name: &str,
+    ) -> AllocatedImage {
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(layers)
+            .samples(samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        // attachments are recreated rarely and are large, dedicated memory lets the driver place them optimally
+        self.create_gpu_image(
+            &image_info,
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                flags: vk_mem::AllocationCreateFlags::DEDICATED_MEMORY,
+                ..Default::default()
+            },
+            name,
+        )
+    }
+
+    // Multisample targets that only live within a render pass, lazily allocated memory where the device has it
+    // (tilers keep them on chip).
+    pub fn create_transient_attachment(
+        self: &Arc<Self>,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        layers: u32,
+        samples: vk::SampleCountFlags,
+        usage: vk::ImageUsageFlags,
+        name: &str,
+    ) -> AllocatedImage {
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D { width: extent.width, height: extent.height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(layers)
+            .samples(samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        self.create_gpu_image(
+            &image_info,
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                preferred_flags: vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+                ..Default::default()
+            },
+            name,
+        )
+    }
+
+    pub fn create_gpu_image(
+        self: &Arc<Self>,
+        image_info: &vk::ImageCreateInfo,
+        allocation_info: &vk_mem::AllocationCreateInfo,
+        name: &str,
+    ) -> AllocatedImage {
+        let (image, allocation) = unsafe { self.allocator.create_image(image_info, allocation_info) }
+            .unwrap_or_else(|err| panic!("Failed to allocate image {}: {:?}", name, err));
+        self.device.track(vk::ObjectType::IMAGE, image.as_raw(), name);
+
+        AllocatedImage {
+            image,
+            format: image_info.format,
+            extent: vk::Extent2D { width: image_info.extent.width, height: image_info.extent.height },
+            allocation,
+            allocator: self.clone(),
+        }
+    }
+
+    // Host visible, persistently mapped buffer the CPU writes sequentially, used as a copy source for uploads.
+    pub fn create_staging_buffer(self: &Arc<Self>, size: vk::DeviceSize, name: &str) -> AllocatedBuffer {
+        self.create_mapped_buffer(
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            name,
+        )
+    }
+
+    // Host visible, persistently mapped buffer the GPU copies into and the CPU reads back.
+    pub fn create_readback_buffer(self: &Arc<Self>, size: vk::DeviceSize, name: &str) -> AllocatedBuffer {
+        self.create_mapped_buffer(
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk_mem::AllocationCreateFlags::HOST_ACCESS_RANDOM,
+            name,
+        )
+    }
+
+    // Persistently mapped uniform buffer, updated by the CPU every frame.
+    pub fn create_uniform_buffer(self: &Arc<Self>, size: vk::DeviceSize, name: &str) -> AllocatedBuffer {
+        self.create_mapped_buffer(
+            size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            name,
+        )
+    }
+
+    // Device local buffer filled through a staging buffer (vertex / index data etc.).
+    pub fn create_gpu_buffer(self: &Arc<Self>, size: vk::DeviceSize, usage: vk::BufferUsageFlags, name: &str) -> AllocatedBuffer {
+        self.create_buffer(
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_DST,
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::AutoPreferDevice,
+                ..Default::default()
+            },
+            name,
+        )
+    }
+
+    // Coherent memory only, so writes through `AllocatedBuffer::mapped` never need an explicit flush.
+    fn create_mapped_buffer(
+        self: &Arc<Self>,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        host_access: vk_mem::AllocationCreateFlags,
+        name: &str,
+    ) -> AllocatedBuffer {
+        self.create_buffer(
+            size,
+            usage,
+            &vk_mem::AllocationCreateInfo {
+                usage: vk_mem::MemoryUsage::Auto,
+                flags: host_access | vk_mem::AllocationCreateFlags::MAPPED,
+                required_flags: vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                ..Default::default()
+            },
+            name,
+        )
+    }
+
+    pub fn create_buffer(
+        self: &Arc<Self>,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        allocation_info: &vk_mem::AllocationCreateInfo,
+        name: &str,
+    ) -> AllocatedBuffer {
+        let buffer_info = vk::BufferCreateInfo::default()
+            .size(size)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let (buffer, allocation, mapped) = unsafe {
+            let (buffer, allocation) = self
+                .allocator
+                .create_buffer(&buffer_info, allocation_info)
+                .unwrap_or_else(|err| panic!("Failed to allocate buffer {}: {:?}", name, err));
+            let mapped = self.allocator.get_allocation_info(&allocation).mapped_data as *mut u8;
+            (buffer, allocation, mapped)
+        };
+        self.device.track(vk::ObjectType::BUFFER, buffer.as_raw(), name);
+
+        AllocatedBuffer {
+            buffer,
+            size,
+            mapped,
+            allocation,
+            allocator: self.clone(),
+        }
+    }
+
+    // Logs per heap budget and usage as reported by vk-mem.
+    pub fn log_statistics(&self) {
+        let budgets = match self.allocator.get_heap_budgets() {
+            Ok(budgets) => budgets,
+            Err(err) => {
+                crit!("Failed to query GPU memory budgets: {:?}", err);
+                return;
+            }
+        };
+
+        info!("GPU memory heaps:");
+        for (index, (heap, budget)) in self.memory_heaps.iter().zip(&budgets).enumerate() {
+            let kind = if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) { "device" } else { "host" };
+            info!(
+                "    heap {} ({}, {} MiB): {} allocations / {} blocks, {} of {} MiB allocated, usage {} of {} MiB budget",
+                index,
+                kind,
+                heap.size >> 20,
+                budget.statistics.allocationCount,
+                budget.statistics.blockCount,
+                budget.statistics.allocationBytes >> 20,
+                budget.statistics.blockBytes >> 20,
+                budget.usage >> 20,
+                budget.budget >> 20,
+            );
+        }
+    }
+}
+
+impl Drop for GpuAllocator {
+    fn drop(&mut self) {
+        // only outlives the device if allocations leaked, those were reported by OwnedDevice already
+        if !self.device.is_destroyed() {
+            unsafe { ManuallyDrop::drop(&mut self.allocator) };
+        }
+    }
+}
+
+
+pub struct AllocatedImage {
+    pub image: vk::Image,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    allocation: vk_mem::Allocation,
+    allocator: Arc<GpuAllocator>,
+}
+
+impl Drop for AllocatedImage {
+    fn drop(&mut self) {
+        self.allocator.device.untrack(vk::ObjectType::IMAGE, self.image.as_raw());
+        if !self.allocator.device.is_destroyed() {
+            unsafe { self.allocator.allocator.destroy_image(self.image, &mut self.allocation) };
+        }
+    }
+}
+
+
+pub struct AllocatedBuffer {
+    pub buffer: vk::Buffer,
+    pub size: vk::DeviceSize,
+    mapped: *mut u8,  // null unless created through one of the mapped helpers
+    allocation: vk_mem::Allocation,
+    allocator: Arc<GpuAllocator>,
+}
+
+// The mapping lives as long as the allocation, synchronizing host writes with the GPU is up to the caller.
+unsafe impl Send for AllocatedBuffer {}
+unsafe impl Sync for AllocatedBuffer {}
+
+impl AllocatedBuffer {
+    pub fn mapped(&self) -> Option<*mut u8> {
+        if self.mapped.is_null() { None } else { Some(self.mapped) }
+    }
+
+    // Copies `data` into the mapped memory at `offset` bytes.
+    pub fn write<T: Copy>(&self, offset: vk::DeviceSize, data: &[T]) {
+        let bytes = std::mem::size_of_val(data) as vk::DeviceSize;
+        assert!(offset + bytes <= self.size, "write of {} bytes at {} overflows buffer of {} bytes", bytes, offset, self.size);
+        let mapped = self.mapped().expect("write to a buffer that isn't host mapped");
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, mapped.add(offset as usize), bytes as usize);
+        }
+    }
+
+    // Mapped memory at `offset`, `len` bytes long.
+    pub fn read(&self, offset: vk::DeviceSize, len: usize) -> &[u8] {
+        assert!(offset + len as vk::DeviceSize <= self.size, "read of {} bytes at {} overflows buffer of {} bytes", len, offset, self.size);
+        let mapped = self.mapped().expect("read from a buffer that isn't host mapped");
+
+        unsafe { std::slice::from_raw_parts(mapped.add(offset as usize), len) }
+    }
+}
+
+impl Drop for AllocatedBuffer {
+    fn drop(&mut self) {
+        self.allocator.device.untrack(vk::ObjectType::BUFFER, self.buffer.as_raw());
+        if !self.allocator.device.is_destroyed() {
+            unsafe { self.allocator.allocator.destroy_buffer(self.buffer, &mut self.allocation) };
+        }
+    }
+}
+
+
+// Sub allocation handed out by FrameRingBuffer, valid until the same frame slot comes around again.
+#[derive(Clone, Copy, Debug)]
+pub struct RingAllocation {
+    pub buffer: vk::Buffer,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    pub ptr: *mut u8,
+}
+
+// Linear allocator for transient per-frame data (uniforms, dynamic vertices) in one persistently mapped buffer
+// split into a region per frame in flight. A region is rewound by begin_frame once its frame's fence signaled.
+pub struct FrameRingBuffer {
+    buffer: AllocatedBuffer,
+    frame_size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    frame_start: vk::DeviceSize,
+    head: vk::DeviceSize,
+}
+
+impl FrameRingBuffer {
+    // `alignment` should be at least the device's minUniformBufferOffsetAlignment when used for uniforms.
+    pub fn new(
+        allocator: &Arc<GpuAllocator>,
+        frame_size: vk::DeviceSize,
+        frames_in_flight: usize,
+        usage: vk::BufferUsageFlags,
+        alignment: vk::DeviceSize,
+        name: &str,
+    ) -> Self {
+        assert!(alignment.is_power_of_two(), "ring buffer alignment must be a power of two");
+        let frame_size = (frame_size + alignment - 1) & !(alignment - 1);
+
+        let buffer = allocator.create_mapped_buffer(
+            frame_size * frames_in_flight as vk::DeviceSize,
+            usage,
+            vk_mem::AllocationCreateFlags::HOST_ACCESS_SEQUENTIAL_WRITE,
+            name,
+        );
+
+        Self {
+            buffer,
+            frame_size,
+            alignment,
+            frame_start: 0,
+            head: 0,
+        }
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer.buffer
+    }
+
+    // Same slot as VulkanSyncObjects::begin_frame returned, everything allocated in that slot before is reused.
+    pub fn begin_frame(&mut self, frame: usize) {
+        self.frame_start = frame as vk::DeviceSize * self.frame_size;
+        self.head = 0;
+    }
+
+    // None when the frame's region is full.
+    pub fn allocate(&mut self, size: vk::DeviceSize) -> Option<RingAllocation> {
+        let offset = (self.head + self.alignment - 1) & !(self.alignment - 1);
+        if offset + size > self.frame_size {
+            return None;
+        }
+        self.head = offset + size;
+
+        let offset = self.frame_start + offset;
+        Some(RingAllocation {
+            buffer: self.buffer.buffer,
+            offset,
+            size,
+            ptr: unsafe { self.buffer.mapped.add(offset as usize) },
+        })
+    }
+
+    // Allocates and copies `data`, e.g. a uniform block for a dynamic offset.
+    pub fn push<T: Copy>(&mut self, data: &[T]) -> Option<RingAllocation> {
+        let allocation = self.allocate(std::mem::size_of_val(data) as vk::DeviceSize)?;
+        self.buffer.write(allocation.offset, data);
+        Some(allocation)
+    }
+}
@@ -1,15 +1,33 @@
 #[allow(non_snake_case)]
 
 // Declare submodules
+pub mod command;
+pub mod config;
 pub mod context;
+pub mod headless;
+pub mod instance;
+pub mod memory;
 pub mod pipeline;
+pub mod pipeline_cache;
+pub mod renderpass;
+pub mod resource;
 pub mod swapchain;
 pub mod shader;
+pub mod sync;
 pub mod utils;
 
 // Re-export items if needed
+pub use command::*;
+pub use config::*;
 pub use context::*;
+pub use headless::*;
+pub use instance::*;
+pub use memory::*;
 pub use pipeline::*;
+pub use pipeline_cache::*;
+pub use renderpass::*;
+pub use resource::*;
 pub use swapchain::*;
 pub use shader::*;
+pub use sync::*;
 pub use utils::*;
@@ -1,184 +1,301 @@
-// use ash::vk;
-// use ash::Device;
-// use std::ffi::CString;
-// use std::marker::PhantomData;
-// use std::ptr;
-// use std::fs::File;
-// use std::io::Read;
-// use std::path::Path;
-// pub struct VulkanGraphicsPipeline<'a> {
-//     pub pipeline: vk::Pipeline,
-//     pub layout: vk::PipelineLayout,
-//     device: &'a Device,
-// }
-
-// impl<'a> VulkanGraphicsPipeline<'a> {
-
-//     fn read_shader_code<P: AsRef<Path>>(path: P) -> Vec<u8> {
-//         let mut file = File::open(path).expect("Failed to open shader file");
-//         let mut code = Vec::new();
-//         file.read_to_end(&mut code).expect("Failed to read shader file");
-//         code
-//     }
-
-//     fn create_shader_module(device: &Device, code: &[u8]) -> vk::ShaderModule {
-//         let code_u32 = ash::util::read_spv(&mut std::io::Cursor::new(code))
-//             .expect("Failed to read shader SPIR-V code");
-    
-//         let create_info = vk::ShaderModuleCreateInfo {
-//             s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
-//             p_next: std::ptr::null(),
-//             flags: vk::ShaderModuleCreateFlags::empty(),
-//             code_size: code_u32.len() * std::mem::size_of::<u32>(),
-//             p_code: code_u32.as_ptr(),
-//             _marker: PhantomData
-//         };
-    
-//         unsafe {
-//             device.create_shader_module(&create_info, None)
-//                 .expect("Failed to create shader module")
-//         }
-//     }
-
-
-
-//     pub fn create(device: &'a Device, swapchain_extent: vk::Extent2D, render_pass: vk::RenderPass) -> Self {
-//         let vert_shader_code = Self::read_shader_code("path/to/vert.spv");
-//         let frag_shader_code = Self::read_shader_code("path/to/frag.spv");
-
-//         let vert_shader_module = Self::create_shader_module(device, &vert_shader_code);
-//         let frag_shader_module = Self::create_shader_module(device, &frag_shader_code);
-
-//         let main_function_name = CString::new("main").unwrap();
-
-//         let vert_shader_stage_info = vk::PipelineShaderStageCreateInfo::default()
-//             .stage(vk::ShaderStageFlags::VERTEX)
-//             .module(vert_shader_module)
-//             .name(&main_function_name);
-
-//         let frag_shader_stage_info = vk::PipelineShaderStageCreateInfo::default()
-//             .stage(vk::ShaderStageFlags::FRAGMENT)
-//             .module(frag_shader_module)
-//             .name(&main_function_name);
-
-//         let shader_stages = [vert_shader_stage_info, frag_shader_stage_info];
-
-//         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default();
-
-//         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
-//             .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-//             .primitive_restart_enable(false);
-
-//         let viewport = vk::Viewport {
-//             x: 0.0,
-//             y: 0.0,
-//             width: swapchain_extent.width as f32,
-//             height: swapchain_extent.height as f32,
-//             min_depth: 0.0,
-//             max_depth: 1.0,
-//         };
-
-//         let scissor = vk::Rect2D {
-//             offset: vk::Offset2D { x: 0, y: 0 },
-//             extent: swapchain_extent,
-//         };
-
-//         let viewports = [viewport];
-//         let scissors = [scissor];
-
-//         let viewport_state = vk::PipelineViewportStateCreateInfo::default()
-//             .viewports(&viewports)
-//             .scissors(&scissors);
-
-//         let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
-//             .depth_clamp_enable(false)
-//             .rasterizer_discard_enable(false)
-//             .polygon_mode(vk::PolygonMode::FILL)
-//             .line_width(1.0)
-//             .cull_mode(vk::CullModeFlags::BACK)
-//             .front_face(vk::FrontFace::CLOCKWISE)
-//             .depth_bias_enable(false);
-
-//         let multisampling = vk::PipelineMultisampleStateCreateInfo::default()
-//             .sample_shading_enable(false)
-//             .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-//         let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
-//             .color_write_mask(vk::ColorComponentFlags::R | vk::ColorComponentFlags::G | vk::ColorComponentFlags::B | vk::ColorComponentFlags::A)
-//             .blend_enable(false);
-
-//         let color_blend_attachments = [color_blend_attachment];
-
-//         let color_blending = vk::PipelineColorBlendStateCreateInfo::default()
-//             .logic_op_enable(false)
-//             .logic_op(vk::logicOp::COPY)
-//             .attachments(&color_blend_attachments)
-//             .blend_constants([0.0, 0.0, 0.0, 0.0]);
-
-//         let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default();
-
-//         let layout = unsafe {
-//             device.create_pipeline_layout(&pipeline_layout_info, None)
-//                 .expect("Failed to create pipeline layout")
-//         };
-
-//         let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
-//             .stages(&shader_stages)
-//             .vertex_input_state(&vertex_input_info)
-//             .input_assembly_state(&input_assembly)
-//             .viewport_state(&viewport_state)
-//             .rasterization_state(&rasterizer)
-//             .multisample_state(&multisampling)
-//             .color_blend_state(&color_blending)
-//             .layout(layout)
-//             .render_pass(render_pass)
-//             .subpass(0);
-
-//         let pipeline = unsafe {
-//             device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-//                 .expect("Failed to create graphics pipeline")[0]
-//         };
-
-//         unsafe {
-//             device.destroy_shader_module(vert_shader_module, None);
-//             device.destroy_shader_module(frag_shader_module, None);
-//         }
-
-//         Self { pipeline, layout, device }
-//     }
-// }
-//     fn read_shader_code<P: AsRef<std::path::Path>>(path: P) -> Vec<u8> {
-//         use std::fs::File;
-//         use std::io::Read;
-//         let mut file = File::open(path).expect("Failed to open shader file");
-//         let mut code = Vec::new();
-//         file.read_to_end(&mut code).expect("Failed to read shader file");
-//         code
-//     }
-
-//     fn create_shader_module(device: &Device, code: &[u8]) -> vk::ShaderModule {
-//         let create_info = vk::ShaderModuleCreateInfo {
-//             s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
-//             p_next: ptr::null(),
-//             flags: vk::ShaderModuleCreateFlags::empty(),
-//             code_size: code.len(),
-//             p_code: code.as_ptr() as *const u32,
-//             _marker: PhantomData
-//         };
-
-//         unsafe {
-//             device.create_shader_module(&create_info, None)
-//                 .expect("Failed to create shader module")
-//         }
-//     }
-
-
-// impl<'a> Drop for VulkanGraphicsPipeline<'a> {
-//     fn drop(&mut self) {
-//         unsafe {
-//             // Assuming `device` is stored somewhere accessible
-//             self.device.destroy_pipeline(self.pipeline, None);
-//             self.device.destroy_pipeline_layout(self.layout, None);
-//         }
-//     }
-// }
+use ash::vk;
+
+use super::config::RenderConfig;
+use super::resource::*;
+
+
+// How the fragment output is combined with the color attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,         // src
+    Alpha,          // src * src_a + dst * (1 - src_a)
+    Premultiplied,  // src + dst * (1 - src_a)
+    Additive,       // src * src_a + dst
+}
+
+impl BlendMode {
+    fn attachment_state(self, color_write_mask: vk::ColorComponentFlags) -> vk::PipelineColorBlendAttachmentState {
+        let (src_color, dst_color) = match self {
+            BlendMode::Opaque => (vk::BlendFactor::ONE, vk::BlendFactor::ZERO),
+            BlendMode::Alpha => (vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Premultiplied => (vk::BlendFactor::ONE, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => (vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE),
+        };
+
+        vk::PipelineColorBlendAttachmentState {
+            blend_enable: if self == BlendMode::Opaque { vk::FALSE } else { vk::TRUE },
+            src_color_blend_factor: src_color,
+            dst_color_blend_factor: dst_color,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: dst_color,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask,
+        }
+    }
+}
+
+
+// Pipeline, layout and the descriptor set layouts it was built with, destroyed in that order.
+pub struct GraphicsPipeline {
+    pub pipeline: OwnedPipeline,
+    pub layout: OwnedPipelineLayout,
+    pub set_layouts: Vec<OwnedDescriptorSetLayout>,
+}
+
+impl GraphicsPipeline {
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline.handle()
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout.handle()
+    }
+
+    pub fn bind(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        unsafe { device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.handle()) };
+    }
+}
+
+
+// Describes a graphics pipeline for subpass 0 of the multiview render pass. Sample count and the depth compare
+// op come from the RenderConfig the render pass was created with, viewport and scissor are always dynamic.
+// build() only borrows the builder, so the same description can be rebuilt with other shader modules.
+#[derive(Clone)]
+pub struct GraphicsPipelineBuilder {
+    render_pass: vk::RenderPass,
+    samples: vk::SampleCountFlags,
+    pipeline_cache: vk::PipelineCache,
+    stages: Vec<(vk::ShaderStageFlags, vk::ShaderModule)>,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare_op: vk::CompareOp,
+    stencil: Option<(vk::StencilOpState, vk::StencilOpState)>,  // (front, back)
+    blend_mode: BlendMode,
+    color_write_mask: vk::ColorComponentFlags,
+    dynamic_states: Vec<vk::DynamicState>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    descriptor_sets: Vec<Vec<vk::DescriptorSetLayoutBinding<'static>>>,
+}
+
+impl GraphicsPipelineBuilder {
+    pub fn new(render_pass: vk::RenderPass, config: &RenderConfig) -> Self {
+        Self {
+            render_pass,
+            samples: config.msaa_samples,
+            pipeline_cache: vk::PipelineCache::null(),
+            stages: Vec::new(),
+            vertex_bindings: Vec::new(),
+            vertex_attributes: Vec::new(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_test: true,
+            depth_write: true,
+            depth_compare_op: config.depth_compare_op(),
+            stencil: None,
+            blend_mode: BlendMode::Opaque,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+            dynamic_states: vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            push_constant_ranges: Vec::new(),
+            descriptor_sets: Vec::new(),
+        }
+    }
+
+    pub fn pipeline_cache(mut self, pipeline_cache: vk::PipelineCache) -> Self {
+        self.pipeline_cache = pipeline_cache;
+        self
+    }
+
+    // Entry point is always "main". Setting a stage twice replaces the module.
+    pub fn shader(mut self, stage: vk::ShaderStageFlags, module: vk::ShaderModule) -> Self {
+        match self.stages.iter_mut().find(|(existing, _)| *existing == stage) {
+            Some(entry) => entry.1 = module,
+            None => self.stages.push((stage, module)),
+        }
+        self
+    }
+
+    // Adds the next vertex buffer binding, `attributes` are (format, offset) pairs. Locations are assigned in
+    // order across all bindings, so the second binding's first attribute follows the last one of the first.
+    pub fn vertex_binding(mut self, stride: u32, input_rate: vk::VertexInputRate, attributes: &[(vk::Format, u32)]) -> Self {
+        let binding = self.vertex_bindings.len() as u32;
+        self.vertex_bindings.push(vk::VertexInputBindingDescription { binding, stride, input_rate });
+
+        for &(format, offset) in attributes {
+            let location = self.vertex_attributes.len() as u32;
+            self.vertex_attributes.push(vk::VertexInputAttributeDescription { location, binding, format, offset });
+        }
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn depth(mut self, test: bool, write: bool) -> Self {
+        self.depth_test = test;
+        self.depth_write = write;
+        self
+    }
+
+    // Overrides the config's op, e.g. EQUAL for a pass after a depth prepass.
+    pub fn depth_compare_op(mut self, compare_op: vk::CompareOp) -> Self {
+        self.depth_compare_op = compare_op;
+        self
+    }
+
+    // Only meaningful if RenderConfig::depth_format has a stencil aspect.
+    pub fn stencil(mut self, front: vk::StencilOpState, back: vk::StencilOpState) -> Self {
+        self.stencil = Some((front, back));
+        self
+    }
+
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn color_write_mask(mut self, color_write_mask: vk::ColorComponentFlags) -> Self {
+        self.color_write_mask = color_write_mask;
+        self
+    }
+
+    // Extra dynamic state on top of viewport / scissor.
+    pub fn dynamic_state(mut self, state: vk::DynamicState) -> Self {
+        if !self.dynamic_states.contains(&state) {
+            self.dynamic_states.push(state);
+        }
+        self
+    }
+
+    pub fn push_constants(mut self, stage_flags: vk::ShaderStageFlags, offset: u32, size: u32) -> Self {
+        self.push_constant_ranges.push(vk::PushConstantRange { stage_flags, offset, size });
+        self
+    }
+
+    // Adds the next descriptor set (set = number of sets added before it), its layout is owned by the pipeline.
+    pub fn descriptor_set(mut self, bindings: &[vk::DescriptorSetLayoutBinding<'static>]) -> Self {
+        self.descriptor_sets.push(bindings.to_vec());
+        self
+    }
+
+    // Objects created before a failure are destroyed again, nothing leaks on Err.
+    pub fn build(&self, device: &OwnedDevice, name: &str) -> Result<GraphicsPipeline, vk::Result> {
+        unsafe {
+            let set_layouts = self
+                .descriptor_sets
+                .iter()
+                .enumerate()
+                .map(|(set, bindings)| {
+                    let set_layout = device
+                        .create_descriptor_set_layout(
+                            &vk::DescriptorSetLayoutCreateInfo::default().bindings(bindings),
+                            None,
+                        )?;
+                    Ok(device.own(set_layout, format!("{} set layout {}", name, set)))
+                })
+                .collect::<Result<Vec<_>, vk::Result>>()?;
+            let set_layout_handles = set_layouts.iter().map(|set_layout| set_layout.handle()).collect::<Vec<_>>();
+
+            let layout = device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo::default()
+                        .set_layouts(&set_layout_handles)
+                        .push_constant_ranges(&self.push_constant_ranges),
+                    None,
+                )?;
+            let layout = device.own(layout, format!("{} layout", name));
+
+            let stages = self
+                .stages
+                .iter()
+                .map(|&(stage, module)| vk::PipelineShaderStageCreateInfo {
+                    stage,
+                    module,
+                    p_name: b"main\0".as_ptr() as _,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+
+            let (front, back) = self.stencil.unwrap_or_default();
+
+            let pipeline = device
+                .create_graphics_pipelines(
+                    self.pipeline_cache,
+                    &[vk::GraphicsPipelineCreateInfo::default()
+                        .stages(&stages)
+                        .vertex_input_state(
+                            &vk::PipelineVertexInputStateCreateInfo::default()
+                                .vertex_binding_descriptions(&self.vertex_bindings)
+                                .vertex_attribute_descriptions(&self.vertex_attributes),
+                        )
+                        .input_assembly_state(
+                            &vk::PipelineInputAssemblyStateCreateInfo::default().topology(self.topology),
+                        )
+                        .viewport_state(
+                            &vk::PipelineViewportStateCreateInfo::default()
+                                .scissor_count(1)
+                                .viewport_count(1),
+                        )
+                        .rasterization_state(
+                            &vk::PipelineRasterizationStateCreateInfo::default()
+                                .cull_mode(self.cull_mode)
+                                .front_face(self.front_face)
+                                .polygon_mode(self.polygon_mode)
+                                .line_width(1.0),
+                        )
+                        .multisample_state(
+                            &vk::PipelineMultisampleStateCreateInfo::default()
+                                .rasterization_samples(self.samples),
+                        )
+                        .depth_stencil_state(
+                            &vk::PipelineDepthStencilStateCreateInfo::default()
+                                .depth_test_enable(self.depth_test)
+                                .depth_write_enable(self.depth_write)
+                                .depth_compare_op(self.depth_compare_op)
+                                .stencil_test_enable(self.stencil.is_some())
+                                .front(front)
+                                .back(back),
+                        )
+                        .color_blend_state(
+                            &vk::PipelineColorBlendStateCreateInfo::default()
+                                .attachments(&[self.blend_mode.attachment_state(self.color_write_mask)]),
+                        )
+                        .dynamic_state(
+                            &vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&self.dynamic_states),
+                        )
+                        .layout(layout.handle())
+                        .render_pass(self.render_pass)
+                        .subpass(0)],
+                    None,
+                )
+                .map_err(|(_, e)| e)?[0];
+
+            Ok(GraphicsPipeline {
+                pipeline: device.own(pipeline, name),
+                layout,
+                set_layouts,
+            })
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use ash::vk;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mlog::*;
+
+use crate::io::file_utils::write_atomically;
+
+use super::resource::*;
+
+
+// VkPipelineCacheHeaderVersionOne: header size, header version, vendor ID, device ID, pipeline cache UUID.
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+
+// VkPipelineCache persisted under target/cache, one file per vendor / device ID pair.
+// The file is only handed to the driver if its header matches this device's vendor, device and cache UUID
+// (the UUID changes with driver updates), some drivers don't survive being given someone else's data.
+// Written back to disk when dropped.
+pub struct PipelineCache {
+    cache: OwnedPipelineCache,
+    path: PathBuf,
+}
+
+impl PipelineCache {
+    pub fn load(instance: &ash::Instance, device: &OwnedDevice, physical_device: vk::PhysicalDevice) -> Result<Self, vk::Result> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("cache")
+            .join(format!("pipeline_cache_{:04x}_{:04x}.bin", properties.vendor_id, properties.device_id));
+
+        let initial_data = match fs::read(&path) {
+            Ok(data) if Self::is_compatible(&data, &properties) => {
+                info!("Loaded pipeline cache {:?} ({} bytes)", path, data.len());
+                data
+            }
+            Ok(_) => {
+                info!("Pipeline cache {:?} is from another device or driver, starting empty", path);
+                Vec::new()
+            }
+            Err(_) => {
+                info!("No pipeline cache at {:?}, starting empty", path);
+                Vec::new()
+            }
+        };
+
+        let cache = unsafe {
+            device
+                .create_pipeline_cache(
+                    &vk::PipelineCacheCreateInfo::default().initial_data(&initial_data),
+                    None,
+                )?
+        };
+
+        Ok(Self {
+            cache: device.own(cache, "pipeline cache"),
+            path,
+        })
+    }
+
+    fn is_compatible(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+        if data.len() < HEADER_SIZE {
+            return false;
+        }
+        let read_u32 = |offset: usize| u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap());
+
+        read_u32(0) as usize >= HEADER_SIZE
+            && read_u32(4) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+            && read_u32(8) == properties.vendor_id
+            && read_u32(12) == properties.device_id
+            && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+    }
+
+    pub fn handle(&self) -> vk::PipelineCache {
+        self.cache.handle()
+    }
+
+    // Written atomically, a crash mid-write never leaves a truncated cache behind.
+    pub fn save(&self) {
+        let data = match unsafe { self.cache.device().get_pipeline_cache_data(self.cache.handle()) } {
+            Ok(data) => data,
+            Err(e) => {
+                crit!("Failed to read pipeline cache data: {}", e);
+                return;
+            }
+        };
+
+        match write_atomically(&self.path, &data) {
+            Ok(()) => info!("Saved pipeline cache {:?} ({} bytes)", self.path, data.len()),
+            Err(e) => crit!("Failed to save pipeline cache {:?}: {}", self.path, e),
+        }
+    }
+}
+
+impl Drop for PipelineCache {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
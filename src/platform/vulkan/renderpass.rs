@@ -0,0 +1,115 @@
+use ash::vk;
+
+use super::context::COLOR_FORMAT;
+
+
+// Single subpass render pass rendering all views at once through VK_KHR_multiview (core in 1.1).
+// Without MSAA attachment 0 is color and 1 is depth, stored so it can be handed to the compositor for reprojection.
+// With MSAA 0 is the transient multisample color, 1 the multisample depth (discarded, 1.1 can't resolve depth)
+// and 2 the single sample color the subpass resolves into.
+pub fn create_multiview_render_pass(
+    device: &ash::Device,
+    view_mask: u32,
+    depth_format: vk::Format,
+    samples: vk::SampleCountFlags,
+) -> Result<vk::RenderPass, vk::Result> {
+    let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+
+    let mut attachments = vec![
+        vk::AttachmentDescription {
+            format: COLOR_FORMAT,
+            samples,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: if multisampled { vk::AttachmentStoreOp::DONT_CARE } else { vk::AttachmentStoreOp::STORE },
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        },
+        vk::AttachmentDescription {
+            format: depth_format,
+            samples,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: if multisampled { vk::AttachmentStoreOp::DONT_CARE } else { vk::AttachmentStoreOp::STORE },
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        },
+    ];
+    if multisampled {
+        attachments.push(vk::AttachmentDescription {
+            format: COLOR_FORMAT,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        });
+    }
+
+    let color_attachments = [vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    }];
+    let depth_attachment = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+    let resolve_attachments = [vk::AttachmentReference {
+        attachment: 2,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    }];
+
+    let mut subpass = vk::SubpassDescription::default()
+        .color_attachments(&color_attachments)
+        .depth_stencil_attachment(&depth_attachment)
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+    if multisampled {
+        subpass = subpass.resolve_attachments(&resolve_attachments);
+    }
+
+    unsafe {
+        device
+            .create_render_pass(
+                &vk::RenderPassCreateInfo::default()
+                    .attachments(&attachments)
+                    .subpasses(&[subpass])
+                    // the previous frame may still be writing the shared multisample targets
+                    .dependencies(&[vk::SubpassDependency {
+                        src_subpass: vk::SUBPASS_EXTERNAL,
+                        dst_subpass: 0,
+                        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                            | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                        src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                        ..Default::default()
+                    }])
+                    .push_next(
+                        &mut vk::RenderPassMultiviewCreateInfo::default()
+                            .view_masks(&[view_mask])
+                            .correlation_masks(&[view_mask]),
+                    ),
+                None,
+            )
+    }
+}
+
+
+// Framebuffer attachments in the order create_multiview_render_pass expects them.
+// `msaa_color` is the multisample target, `color` the single sample image it resolves into.
+pub fn multiview_attachments(
+    color: vk::ImageView,
+    depth: vk::ImageView,
+    msaa_color: Option<vk::ImageView>,
+) -> Vec<vk::ImageView> {
+    match msaa_color {
+        Some(msaa_color) => vec![msaa_color, depth, color],
+        None => vec![color, depth],
+    }
+}
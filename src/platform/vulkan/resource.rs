@@ -0,0 +1,221 @@
+use ash::vk;
+use ash::vk::Handle;
+
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use mlog::*;
+
+
+// Device level Vulkan object that can be destroyed through ash::Device.
+pub trait DeviceObject: Handle + Copy {
+    unsafe fn destroy(self, device: &ash::Device);
+}
+
+macro_rules! device_object {
+    ($($object:ty => $destroy:ident),* $(,)?) => {
+        $(impl DeviceObject for $object {
+            unsafe fn destroy(self, device: &ash::Device) {
+                device.$destroy(self, None);
+            }
+        })*
+    };
+}
+
+device_object! {
+    vk::Buffer => destroy_buffer,
+    vk::CommandPool => destroy_command_pool,
+    vk::DescriptorPool => destroy_descriptor_pool,
+    vk::DescriptorSetLayout => destroy_descriptor_set_layout,
+    vk::DeviceMemory => free_memory,
+    vk::Fence => destroy_fence,
+    vk::Framebuffer => destroy_framebuffer,
+    vk::Image => destroy_image,
+    vk::ImageView => destroy_image_view,
+    vk::Pipeline => destroy_pipeline,
+    vk::PipelineCache => destroy_pipeline_cache,
+    vk::PipelineLayout => destroy_pipeline_layout,
+    vk::RenderPass => destroy_render_pass,
+    vk::Sampler => destroy_sampler,
+    vk::Semaphore => destroy_semaphore,
+    vk::ShaderModule => destroy_shader_module,
+}
+
+
+// ash::Device shared by every Owned object, plus the list of objects still alive for the leak report.
+pub struct TrackedDevice {
+    device: ash::Device,
+    live: Mutex<BTreeMap<(i32, u64), String>>,  // (object type, raw handle) -> name
+    destroyed: AtomicBool,
+}
+
+impl TrackedDevice {
+    // Registers an object destroyed by something other than Owned (e.g. vk-mem allocations) for the leak report.
+    pub fn track(&self, object_type: vk::ObjectType, raw: u64, name: impl Into<String>) {
+        self.live.lock().unwrap().insert((object_type.as_raw(), raw), name.into());
+    }
+
+    pub fn untrack(&self, object_type: vk::ObjectType, raw: u64) {
+        self.live.lock().unwrap().remove(&(object_type.as_raw(), raw));
+    }
+
+    // Set once OwnedDevice has destroyed the device, anything dropped after that must not touch it.
+    pub fn is_destroyed(&self) -> bool {
+        self.destroyed.load(Ordering::Acquire)
+    }
+}
+
+impl Deref for TrackedDevice {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &ash::Device {
+        &self.device
+    }
+}
+
+
+// Owns the logical device. Dropping it waits for the GPU, reports every Owned object that is still alive
+// (those are leaked, not destroyed) and destroys the device.
+pub struct OwnedDevice {
+    shared: Arc<TrackedDevice>,
+}
+
+impl OwnedDevice {
+    pub fn new(device: ash::Device) -> Self {
+        Self {
+            shared: Arc::new(TrackedDevice {
+                device,
+                live: Mutex::new(BTreeMap::new()),
+                destroyed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn shared(&self) -> Arc<TrackedDevice> {
+        self.shared.clone()
+    }
+
+    // Takes ownership of `handle`, it is destroyed when the returned object is dropped.
+    pub fn own<T: DeviceObject>(&self, handle: T, name: impl Into<String>) -> Owned<T> {
+        Owned::new(&self.shared, handle, name)
+    }
+
+    // Objects that haven't been dropped yet, as (type, raw handle, name).
+    pub fn live_objects(&self) -> Vec<(vk::ObjectType, u64, String)> {
+        self.shared
+            .live
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&(object_type, raw), name)| (vk::ObjectType::from_raw(object_type), raw, name.clone()))
+            .collect()
+    }
+}
+
+impl Deref for OwnedDevice {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &ash::Device {
+        &self.shared.device
+    }
+}
+
+impl Drop for OwnedDevice {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.shared.device.device_wait_idle();
+        }
+
+        let leaks = self.live_objects();
+        if !leaks.is_empty() {
+            crit!("{} Vulkan objects still alive at device teardown:", leaks.len());
+            for (object_type, raw, name) in &leaks {
+                crit!("    {:?} {:#x} \"{}\"", object_type, raw, name);
+            }
+        }
+
+        self.shared.destroyed.store(true, Ordering::Release);
+        unsafe { self.shared.device.destroy_device(None) };
+    }
+}
+
+
+// Typed owning wrapper for a device object, destroyed on drop.
+pub struct Owned<T: DeviceObject> {
+    handle: T,
+    device: Arc<TrackedDevice>,
+}
+
+pub type OwnedBuffer = Owned<vk::Buffer>;
+pub type OwnedCommandPool = Owned<vk::CommandPool>;
+pub type OwnedDescriptorSetLayout = Owned<vk::DescriptorSetLayout>;
+pub type OwnedDeviceMemory = Owned<vk::DeviceMemory>;
+pub type OwnedFence = Owned<vk::Fence>;
+pub type OwnedFramebuffer = Owned<vk::Framebuffer>;
+pub type OwnedImage = Owned<vk::Image>;
+pub type OwnedImageView = Owned<vk::ImageView>;
+pub type OwnedPipeline = Owned<vk::Pipeline>;
+pub type OwnedPipelineCache = Owned<vk::PipelineCache>;
+pub type OwnedPipelineLayout = Owned<vk::PipelineLayout>;
+pub type OwnedRenderPass = Owned<vk::RenderPass>;
+pub type OwnedSemaphore = Owned<vk::Semaphore>;
+pub type OwnedShaderModule = Owned<vk::ShaderModule>;
+
+impl<T: DeviceObject> Owned<T> {
+    pub fn new(device: &Arc<TrackedDevice>, handle: T, name: impl Into<String>) -> Self {
+        device.track(T::TYPE, handle.as_raw(), name);
+
+        Self {
+            handle,
+            device: device.clone(),
+        }
+    }
+
+    pub fn handle(&self) -> T {
+        self.handle
+    }
+
+    pub fn device(&self) -> &ash::Device {
+        &self.device
+    }
+}
+
+impl<T: DeviceObject> Drop for Owned<T> {
+    fn drop(&mut self) {
+        self.device.untrack(T::TYPE, self.handle.as_raw());
+
+        // already reported as leaked by OwnedDevice, the device is gone
+        if self.device.is_destroyed() {
+            return;
+        }
+        unsafe { self.handle.destroy(&self.device) };
+    }
+}
+
+
+// Owns the Vulkan instance, has to outlive the OwnedDevice created from it.
+pub struct OwnedInstance {
+    instance: ash::Instance,
+}
+
+impl OwnedInstance {
+    pub fn new(instance: ash::Instance) -> Self {
+        Self { instance }
+    }
+}
+
+impl Deref for OwnedInstance {
+    type Target = ash::Instance;
+
+    fn deref(&self) -> &ash::Instance {
+        &self.instance
+    }
+}
+
+impl Drop for OwnedInstance {
+    fn drop(&mut self) {
+        unsafe { self.instance.destroy_instance(None) };
+    }
+}
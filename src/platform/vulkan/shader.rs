@@ -3,24 +3,50 @@ use ash::vk;
 use ash::vk::Device;
 use ash::vk::ShaderModuleCreateInfo;
 
+use std::fmt;
+
 use crate::io::shader_compiler::load_spirv_from_file;
 
+use super::resource::{OwnedDevice, OwnedShaderModule};
+
+
+// Sources of the fullscreen debug pipeline, relative to resources/shaders.
+pub const DEBUG_VERT_SHADER: &str = "fullscreen.vert";
+pub const DEBUG_FRAG_SHADER: &str = "debug_pattern.frag";
+
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Spirv(String, std::io::Error),  // .spv missing, unreadable or not SPIR-V (e.g. truncated mid-write)
+    Vulkan(String, vk::Result),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Spirv(path, e) => write!(f, "failed to load SPIR-V {}: {}", path, e),
+            ShaderError::Vulkan(path, e) => write!(f, "failed to create shader module from {}: {}", path, e),
+        }
+    }
+}
+
 
 //  load preset shader modules for now.               -> (vertex shader, fragment shader)
-pub fn create_shader_modules(vk_device: &ash::Device) -> (vk::ShaderModule, vk::ShaderModule) {
-    // Call the new function to load SPIR-V files
-    let vert_spv = load_spirv_from_file("resources/shaders/fullscreen.vert.spv");
-    let frag_spv = load_spirv_from_file("resources/shaders/debug_pattern.frag.spv");
-
-    // Create shader modules using the SPIR-V bytecode
-    // Create shader modules
-    let vert_module = unsafe {vk_device
-        .create_shader_module(&ShaderModuleCreateInfo::default().code(&vert_spv), None)
-        .expect("Failed to create vertex shader module")};
-    let frag_module = unsafe { vk_device
-        .create_shader_module(&ShaderModuleCreateInfo::default().code(&frag_spv), None)
-        .expect("Failed to create fragment shader module") };
-
-    
-    (vert_module, frag_module)
-}
\ No newline at end of file
+pub fn create_shader_modules(device: &OwnedDevice) -> Result<(OwnedShaderModule, OwnedShaderModule), ShaderError> {
+    let vert_module = device.own(create_shader_module(device, DEBUG_VERT_SHADER)?, DEBUG_VERT_SHADER);
+    let frag_module = device.own(create_shader_module(device, DEBUG_FRAG_SHADER)?, DEBUG_FRAG_SHADER);
+    Ok((vert_module, frag_module))
+}
+
+
+// Loads the compiled <source>.spv for a shader source in resources/shaders.
+pub fn create_shader_module(vk_device: &ash::Device, source: &str) -> Result<vk::ShaderModule, ShaderError> {
+    let path = format!("resources/shaders/{}.spv", source);
+    let spv = load_spirv_from_file(&path).map_err(|e| ShaderError::Spirv(path.clone(), e))?;
+
+    unsafe {
+        vk_device
+            .create_shader_module(&ShaderModuleCreateInfo::default().code(&spv), None)
+            .map_err(|e| ShaderError::Vulkan(path, e))
+    }
+}
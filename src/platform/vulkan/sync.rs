@@ -0,0 +1,134 @@
+use ash::vk;
+
+#[cfg(any(debug_assertions, feature = "build_debug"))]
+use mlog::*;
+
+use super::resource::{OwnedDevice, OwnedFence};
+
+// Synchronization for N frames in flight over a swapchain with M images:
+//  - in_flight_fences[frame] throttles the CPU to at most N frames ahead of the GPU
+//  - images_in_flight[image] remembers the fence of the frame last rendering into an image, so an image is
+//    never re-recorded while an older frame still uses it (M and N are independent)
+//  - command buffers come from CommandPoolManager, whose pools for a slot may be reset once begin_frame returned
+// Frames are handed over by releasing the OpenXR swapchain image, the runtime synchronizes on the queue itself,
+// so no semaphores are involved.
+// Debug builds check at every point a slot's resources are reused that the GPU is done with them.
+pub struct VulkanSyncObjects {
+    current_frame: usize,
+    in_flight_fences: Vec<OwnedFence>,
+    images_in_flight: Vec<vk::Fence>,  // borrowed from in_flight_fences
+}
+
+impl Default for VulkanSyncObjects {
+    fn default() -> Self {
+        Self {
+            current_frame: 0,
+            in_flight_fences: Vec::new(),
+            images_in_flight: Vec::new(),
+        }
+    }
+}
+
+impl VulkanSyncObjects {
+    pub fn new(device: &OwnedDevice, frames_in_flight: usize, image_count: usize) -> Self {
+        assert!(frames_in_flight > 0, "At least one frame in flight is required");
+
+        let mut sync = Self::default();
+        unsafe {
+            for frame in 0..frames_in_flight {
+                // signaled so the first wait on every slot returns immediately
+                sync.in_flight_fences.push(device.own(
+                    device
+                        .create_fence(&vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED), None)
+                        .expect("Failed to create in flight fence"),
+                    format!("in flight fence {}", frame),
+                ));
+            }
+        }
+        sync.images_in_flight = vec![vk::Fence::null(); image_count];
+        sync
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.in_flight_fences.len()
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    // Blocks until the GPU has finished the frame that last used the current slot.
+    // Returns the slot, after which CommandPoolManager::begin_frame may recycle its command pools.
+    pub fn begin_frame(&mut self, device: &ash::Device) -> usize {
+        let fence = self.in_flight_fences[self.current_frame].handle();
+        unsafe {
+            device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .expect("Failed to wait for in flight fence");
+        }
+        self.current_frame
+    }
+
+    // Call once the swapchain image index is known, before recording into it.
+    pub fn wait_for_image(&mut self, device: &ash::Device, image_index: usize) {
+        let image_fence = self.images_in_flight[image_index];
+        if image_fence != vk::Fence::null() {
+            unsafe {
+                device
+                    .wait_for_fences(&[image_fence], true, u64::MAX)
+                    .expect("Failed to wait for swapchain image fence");
+            }
+        }
+
+        // the image is now tied to this slot's fence, which must not still guard an earlier submission
+        let fence = self.in_flight_fences[self.current_frame].handle();
+        self.validate_idle(device, fence, "swapchain image");
+        self.images_in_flight[image_index] = fence;
+    }
+
+    // Submits the command buffers recorded for the current slot and advances to the next slot.
+    pub fn submit(&mut self, device: &ash::Device, queue: vk::Queue, command_buffers: &[vk::CommandBuffer]) {
+        let fence = self.in_flight_fences[self.current_frame].handle();
+        let submit_info = vk::SubmitInfo::default().command_buffers(command_buffers);
+
+        unsafe {
+            device.reset_fences(&[fence]).expect("Failed to reset in flight fence");
+            device
+                .queue_submit(queue, &[submit_info], fence)
+                .expect("Failed to submit frame");
+        }
+
+        self.current_frame = (self.current_frame + 1) % self.frames_in_flight();
+    }
+
+    // Debug builds: panics if a resource of the current slot is about to be reused while the GPU still owns it,
+    // e.g. because begin_frame was skipped. Compiled out of release builds without build_debug.
+    pub fn validate_slot_idle(&self, device: &ash::Device, resource: &str) {
+        self.validate_idle(device, self.in_flight_fences[self.current_frame].handle(), resource);
+    }
+
+    #[cfg(any(debug_assertions, feature = "build_debug"))]
+    fn validate_idle(&self, device: &ash::Device, fence: vk::Fence, resource: &str) {
+        let signaled = unsafe { device.get_fence_status(fence) }.unwrap_or(false);
+        if !signaled {
+            crit!("Frame {}: {} reused while still in use by the GPU", self.current_frame, resource);
+            panic!("GPU resource reused while in flight");
+        }
+    }
+
+    #[cfg(not(any(debug_assertions, feature = "build_debug")))]
+    fn validate_idle(&self, _device: &ash::Device, _fence: vk::Fence, _resource: &str) {}
+}
+
+// Waits for all frames to finish, the fences are destroyed right after. A lost device is not worth a panic while
+// dropping, the wait result is ignored.
+impl Drop for VulkanSyncObjects {
+    fn drop(&mut self) {
+        if let Some(first) = self.in_flight_fences.first() {
+            let fences = self.in_flight_fences.iter().map(|fence| fence.handle()).collect::<Vec<_>>();
+            unsafe {
+                let _ = first.device().wait_for_fences(&fences, true, u64::MAX);
+            }
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use ::winit::dpi::LogicalSize;
+use ::winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent,
+};
+use ::winit::event_loop::EventLoop;
+use ::winit::platform::run_return::EventLoopExtRunReturn;
+use ::winit::window::{CursorGrabMode, Window, WindowBuilder};
+use openxr as xr;
+use mlog::*;
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::platform::openxr::device_emulation::{DeviceManager, HEAD_DEVICE, LEFT_HAND_DEVICE, RIGHT_HAND_DEVICE};
+use crate::platform::openxr::motion::{pose_mul, quat_from_axis_angle, quat_mul, quat_rotate, vec3_add, vec3_scale, X_AXIS, Y_AXIS};
+
+const HEAD_HEIGHT: f32 = 1.7;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlTarget {
+    Head,
+    LeftHand,   // while Ctrl is held
+    RightHand,  // while Alt is held
+}
+
+impl ControlTarget {
+    pub fn device_name(&self) -> &'static str {
+        match self {
+            ControlTarget::Head => HEAD_DEVICE,
+            ControlTarget::LeftHand => LEFT_HAND_DEVICE,
+            ControlTarget::RightHand => RIGHT_HAND_DEVICE,
+        }
+    }
+}
+
+
+// Position + yaw / pitch, the head is in stage space and the hands are relative to the head.
+#[derive(Clone, Copy, Debug)]
+struct EmulatedPose {
+    position: xr::Vector3f,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl EmulatedPose {
+    fn to_pose(self) -> xr::Posef {
+        xr::Posef {
+            orientation: quat_mul(quat_from_axis_angle(Y_AXIS, self.yaw), quat_from_axis_angle(X_AXIS, self.pitch)),
+            position: self.position,
+        }
+    }
+}
+
+
+// Keyboard + mouse mapping onto the emulated HMD and controllers:
+//   WASD          move forward / left / back / right, Space / C up / down
+//   mouse         look (after clicking into the window, Escape releases the cursor)
+//   Ctrl / Alt    hold to control the left / right controller instead of the head
+//   mouse buttons left = trigger, right = grip, middle = menu on the controlled hand (right hand while controlling the head)
+pub struct DesktopInput {
+    pub move_speed: f32,  // meters per second
+    pub look_sensitivity: f32,  // radians per mouse unit
+    pressed_keys: HashSet<VirtualKeyCode>,
+    modifiers: ModifiersState,
+    cursor_captured: bool,
+    head: EmulatedPose,
+    left_hand: EmulatedPose,
+    right_hand: EmulatedPose,
+}
+
+impl DesktopInput {
+    pub fn new() -> Self {
+        Self {
+            move_speed: 1.5,
+            look_sensitivity: 0.002,
+            pressed_keys: HashSet::new(),
+            modifiers: ModifiersState::empty(),
+            cursor_captured: false,
+            head: EmulatedPose {
+                position: xr::Vector3f { x: 0.0, y: HEAD_HEIGHT, z: 0.0 },
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+            left_hand: EmulatedPose {
+                position: xr::Vector3f { x: -0.2, y: -0.4, z: -0.35 },
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+            right_hand: EmulatedPose {
+                position: xr::Vector3f { x: 0.2, y: -0.4, z: -0.35 },
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+        }
+    }
+
+    pub fn control_target(&self) -> ControlTarget {
+        if self.modifiers.ctrl() {
+            ControlTarget::LeftHand
+        } else if self.modifiers.alt() {
+            ControlTarget::RightHand
+        } else {
+            ControlTarget::Head
+        }
+    }
+
+    pub fn is_cursor_captured(&self) -> bool {
+        self.cursor_captured
+    }
+
+    fn controlled_pose(&mut self) -> &mut EmulatedPose {
+        match self.control_target() {
+            ControlTarget::Head => &mut self.head,
+            ControlTarget::LeftHand => &mut self.left_hand,
+            ControlTarget::RightHand => &mut self.right_hand,
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent, window: &Window, device_manager: &mut DeviceManager) {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+            }
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput { virtual_keycode: Some(key), state, .. },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    if *key == VirtualKeyCode::Escape {
+                        self.set_cursor_captured(window, false);
+                    }
+                    self.pressed_keys.insert(*key);
+                }
+                ElementState::Released => {
+                    self.pressed_keys.remove(key);
+                }
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                if !self.cursor_captured {
+                    if *state == ElementState::Pressed {
+                        self.set_cursor_captured(window, true);
+                    }
+                    return;
+                }
+
+                let button_name = match button {
+                    MouseButton::Left => "trigger",
+                    MouseButton::Right => "grip",
+                    MouseButton::Middle => "menu",
+                    MouseButton::Other(_) => return,
+                };
+
+                let hand = match self.control_target() {
+                    ControlTarget::LeftHand => LEFT_HAND_DEVICE,
+                    _ => RIGHT_HAND_DEVICE,
+                };
+
+                device_manager
+                    .ensure_device(hand)
+                    .set_button(button_name, *state == ElementState::Pressed);
+            }
+            WindowEvent::Focused(false) => {
+                self.pressed_keys.clear();
+                self.set_cursor_captured(window, false);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        if !self.cursor_captured {
+            return;
+        }
+
+        let sensitivity = self.look_sensitivity;
+        let pose = self.controlled_pose();
+        pose.yaw -= delta.0 as f32 * sensitivity;
+        pose.pitch = (pose.pitch - delta.1 as f32 * sensitivity).clamp(-1.5, 1.5);
+    }
+
+    // Integrates held keys over `dt` and writes the resulting poses into the device manager at `time`.
+    // Devices currently posed over remote control are skipped, see RemoteCommand::Release.
+    pub fn update(&mut self, dt: Duration, time: Duration, device_manager: &mut DeviceManager) {
+        let key_axis = |positive: VirtualKeyCode, negative: VirtualKeyCode| {
+            (self.pressed_keys.contains(&positive) as i32 - self.pressed_keys.contains(&negative) as i32) as f32
+        };
+
+        let local_direction = xr::Vector3f {
+            x: key_axis(VirtualKeyCode::D, VirtualKeyCode::A),
+            y: key_axis(VirtualKeyCode::Space, VirtualKeyCode::C),
+            z: key_axis(VirtualKeyCode::S, VirtualKeyCode::W),
+        };
+        let step = self.move_speed * dt.as_secs_f32();
+
+        let head_yaw = quat_from_axis_angle(Y_AXIS, self.head.yaw);
+        match self.control_target() {
+            // head moves along the ground plane in the direction it is facing
+            ControlTarget::Head => {
+                self.head.position = vec3_add(self.head.position, vec3_scale(quat_rotate(head_yaw, local_direction), step));
+            }
+            // hands move in head space
+            _ => {
+                let pose = self.controlled_pose();
+                pose.position = vec3_add(pose.position, vec3_scale(local_direction, step));
+            }
+        }
+
+        let head_pose = self.head.to_pose();
+        for (device_name, pose) in [
+            (HEAD_DEVICE, head_pose),
+            (LEFT_HAND_DEVICE, pose_mul(head_pose, self.left_hand.to_pose())),
+            (RIGHT_HAND_DEVICE, pose_mul(head_pose, self.right_hand.to_pose())),
+        ] {
+            if device_manager.ensure_device(device_name).remote_controlled {
+                continue;
+            }
+            let _ = device_manager.update_pose(device_name, pose, time);
+        }
+    }
+
+    fn set_cursor_captured(&mut self, window: &Window, captured: bool) {
+        if captured == self.cursor_captured {
+            return;
+        }
+
+        let result = if captured {
+            window
+                .set_cursor_grab(CursorGrabMode::Confined)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+
+        if let Err(e) = result {
+            info!("Cursor grab unavailable: {}", e);
+        }
+
+        window.set_cursor_visible(!captured);
+        self.cursor_captured = captured;
+    }
+}
+
+
+// Opens a desktop window driving `device_manager` from keyboard and mouse, calling `on_frame` once per
+// iteration with the time since start. The window only captures input, nothing is presented to it.
+// Returns once the window is closed, so the caller can tear down in order.
+pub fn run_desktop_emulation(device_manager: &mut DeviceManager, mut on_frame: impl FnMut(&mut DeviceManager, Duration)) {
+    let mut event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("neon - device emulation")
+        .with_inner_size(LogicalSize::new(1280.0, 720.0))
+        .build(&event_loop)
+        .expect("Failed to create emulation window");
+
+    info!("Desktop emulation (input only, frames are rendered offscreen): WASD + mouse to move, hold Ctrl / Alt for left / right controller, click to capture cursor");
+
+    let mut input = DesktopInput::new();
+    let start = Instant::now();
+    let mut last_frame = start;
+
+    event_loop.run_return(|event, _, control_flow| {
+        control_flow.set_poll();
+
+        match event {
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                success!("Emulation window closed");
+                control_flow.set_exit();
+            }
+            Event::WindowEvent { event, .. } => {
+                input.handle_window_event(&event, &window, device_manager);
+            }
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                input.handle_mouse_motion(delta);
+            }
+            Event::MainEventsCleared => {
+                let now = Instant::now();
+                input.update(now - last_frame, now - start, device_manager);
+                last_frame = now;
+
+                on_frame(device_manager, now - start);
+            }
+            _ => {}
+        }
+    });
+}